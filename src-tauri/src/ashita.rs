@@ -376,6 +376,11 @@ async fn make_script_file(profile: &Profile, profile_filename: &str) -> anyhow::
 const XIL_START: &'static str = "\n## XI_LAUNCHER START";
 const XIL_END: &'static str = "\n## XI_LAUNCHER END";
 
+/// Whether the script has the section the launcher manages, so it is updated instead of replaced.
+pub(crate) fn has_launcher_section(script: &str) -> bool {
+    script.contains(XIL_START)
+}
+
 async fn new_script_file(profile: &Profile, script_path: &PathBuf) -> anyhow::Result<()> {
     fs::create_dir_all(script_path.parent().unwrap()).await?;
     let mut file = File::create(&script_path)
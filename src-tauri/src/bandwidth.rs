@@ -0,0 +1,152 @@
+use std::{
+    sync::{LazyLock, RwLock},
+    time::{Duration, Instant},
+};
+
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::sync::Mutex;
+
+/// Longest a download waits before checking whether the limit has changed.
+const MAX_WAIT: Duration = Duration::from_millis(250);
+
+static LIMITER: LazyLock<BandwidthLimiter> = LazyLock::new(BandwidthLimiter::default);
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct BandwidthSettings {
+    /// Combined download speed of all downloads in KiB/s, or unlimited if not set.
+    #[serde(default)]
+    pub limit_kib_per_sec: Option<u64>,
+
+    /// Hours of the day in which downloads are not limited.
+    #[serde(default)]
+    pub unlimited_hours: Option<BandwidthSchedule>,
+}
+
+/// Local hours from `start_hour` up to `end_hour`, wrapping past midnight if `end_hour` is earlier.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type)]
+pub struct BandwidthSchedule {
+    pub start_hour: u8,
+    pub end_hour: u8,
+}
+
+impl BandwidthSchedule {
+    pub fn contains(&self, hour: u8) -> bool {
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+impl BandwidthSettings {
+    /// Returns the limit in bytes per second that applies at the given local hour.
+    pub fn bytes_per_second_at(&self, hour: u8) -> Option<u64> {
+        if self
+            .unlimited_hours
+            .is_some_and(|schedule| schedule.contains(hour))
+        {
+            return None;
+        }
+
+        self.limit_kib_per_sec
+            .filter(|limit| *limit > 0)
+            .map(|limit| limit * 1024)
+    }
+}
+
+/// Token bucket shared by every download, so concurrent downloads split the limit between them.
+#[derive(Default)]
+pub struct BandwidthLimiter {
+    settings: RwLock<BandwidthSettings>,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Default)]
+struct Bucket {
+    /// Bytes that can be downloaded right away. Negative when a large chunk went over the limit.
+    available: f64,
+    refilled_at: Option<Instant>,
+}
+
+/// The limiter used by all downloads.
+pub fn limiter() -> &'static BandwidthLimiter {
+    &LIMITER
+}
+
+impl BandwidthLimiter {
+    /// Changes the limit, also for downloads that are already running.
+    pub fn configure(&self, settings: &BandwidthSettings) {
+        *self.settings.write().unwrap() = settings.clone();
+    }
+
+    fn current_bytes_per_second(&self) -> Option<u64> {
+        let hour = chrono::Local::now().hour() as u8;
+        self.settings.read().unwrap().bytes_per_second_at(hour)
+    }
+
+    /// Waits until `length` more bytes can be downloaded without going over the limit.
+    pub async fn acquire(&self, length: u64) {
+        let mut bucket = self.bucket.lock().await;
+
+        loop {
+            let now = Instant::now();
+            let Some(rate) = self.current_bytes_per_second() else {
+                bucket.available = 0.0;
+                bucket.refilled_at = None;
+                return;
+            };
+
+            let rate = rate as f64;
+            let elapsed = bucket
+                .refilled_at
+                .map(|refilled_at| (now - refilled_at).as_secs_f64())
+                .unwrap_or_default();
+            // Allow a burst of at most a second, so pausing doesn't build up unlimited credit
+            bucket.available = (bucket.available + elapsed * rate).min(rate);
+            bucket.refilled_at = Some(now);
+
+            if bucket.available >= 0.0 {
+                bucket.available -= length as f64;
+                return;
+            }
+
+            let wait = Duration::from_secs_f64(-bucket.available / rate).min(MAX_WAIT);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_wraps_past_midnight() {
+        let schedule = BandwidthSchedule {
+            start_hour: 23,
+            end_hour: 7,
+        };
+        assert!(schedule.contains(23));
+        assert!(schedule.contains(0));
+        assert!(schedule.contains(6));
+        assert!(!schedule.contains(7));
+        assert!(!schedule.contains(12));
+    }
+
+    #[test]
+    fn limit_is_lifted_during_schedule() {
+        let settings = BandwidthSettings {
+            limit_kib_per_sec: Some(512),
+            unlimited_hours: Some(BandwidthSchedule {
+                start_hour: 1,
+                end_hour: 6,
+            }),
+        };
+        assert_eq!(settings.bytes_per_second_at(0), Some(512 * 1024));
+        assert_eq!(settings.bytes_per_second_at(3), None);
+        assert_eq!(BandwidthSettings::default().bytes_per_second_at(3), None);
+    }
+}
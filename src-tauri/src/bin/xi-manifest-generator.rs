@@ -0,0 +1,3 @@
+fn main() -> anyhow::Result<()> {
+    xi_launcher_lib::manifest_generator::main()
+}
@@ -1,13 +1,28 @@
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::anyhow;
-use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Context};
+use minisign_verify::PublicKey;
+use reqwest::{
+    header::{HeaderName, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use schemars::{schema_for, JsonSchema};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use specta::Type;
 use tokio::fs::{self};
 
 use crate::{
-    config::profiles::{AuthKind, Profile},
+    client_version::{detect_client_version, ClientRequirement, ClientVersion},
+    config::profiles::{AuthKind, OfflinePolicy, Profile},
+    filename, http,
+    manifest_cache::{CachedManifest, ManifestCache},
+    signature,
     state::AppState,
+    version::{compare_versions, VersionChange},
 };
 
 #[derive(Debug, Clone, Serialize, Type)]
@@ -15,33 +30,127 @@ use crate::{
 pub enum LaunchStatus {
     NeedsGameDir,
     NeedsInstall,
-    NeedsAndCanInstall { download_info: DownloadInfo },
+    NeedsAndCanInstall {
+        download_info: DownloadInfo,
+    },
     NeedsAshita,
     NeedsWindower,
-    NeedsUpdate { versions_info: VersionsInfo },
+    NeedsUpdate {
+        versions_info: VersionsInfo,
+        /// Components the server has an older version of, which need consent to be downgraded.
+        downgrades: Vec<String>,
+    },
+    VerificationFailed {
+        reason: String,
+    },
+    /// The server could not be asked for updates, so installed files may be out of date.
+    UpdateCheckFailed {
+        reason: String,
+        error: ServerErrorDetails,
+        policy: OfflinePolicy,
+        /// The last manifest fetched from the server, if any.
+        last_known_versions: Option<VersionsInfo>,
+        /// Installed versions of the components in the last known manifest.
+        installed_versions: BTreeMap<String, String>,
+        needs_password: bool,
+    },
+    /// The installed client doesn't match the client version the server declares.
+    ClientVersionMismatch {
+        reason: String,
+        installed: Option<ClientVersion>,
+        required: ClientRequirement,
+        /// Whether the mismatch is only a warning, and the game can still be launched.
+        can_launch: bool,
+        needs_password: bool,
+    },
     NeedsPassword,
     Ready,
 }
 
+impl LaunchStatus {
+    /// Why the game must not be launched with this status, if it must not.
+    pub fn launch_blocker(&self) -> Option<String> {
+        match self {
+            LaunchStatus::VerificationFailed { reason } => Some(format!(
+                "The server's files could not be verified: {reason}"
+            )),
+            LaunchStatus::UpdateCheckFailed {
+                reason,
+                policy: OfflinePolicy::Block,
+                ..
+            } => Some(format!(
+                "The profile doesn't allow launching without checking the server for updates: {reason}"
+            )),
+            LaunchStatus::ClientVersionMismatch {
+                reason,
+                can_launch: false,
+                ..
+            } => Some(reason.clone()),
+            _ => None,
+        }
+    }
+}
+
+const OFFLINE_RETRY_ATTEMPTS: u32 = 3;
+const OFFLINE_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Checks what is needed to launch the profile, and remembers whether the result allows launching it.
 pub async fn check_game_launch(id: u32, state: AppState<'_>) -> anyhow::Result<LaunchStatus> {
+    // The server is asked without holding the lock, since it may take a while to answer or be retried
+    let (profile, mut cache, max_age) = {
+        let state = state.read().await;
+        let profile = state
+            .profiles
+            .map
+            .get(&id)
+            .cloned()
+            .ok_or(anyhow!("No profile with the given index found."))?;
+
+        let max_age = state.config.get_manifest_cache_ttl();
+        (profile, state.manifest_cache.clone(), max_age)
+    };
+
+    let status = get_launch_status(&profile, &mut cache, max_age).await;
+
     let mut state = state.write().await;
+    state.manifest_cache.merge(cache);
+    if let Err(err) = state.manifest_cache.save(&state.local_data_dir) {
+        tracing::warn!("Could not save manifest cache: {err:?}");
+    }
 
-    let profile = state
-        .profiles
-        .map
-        .get(&id)
-        .ok_or(anyhow!("No profile with the given index found."))?;
+    let status = status?;
+    state.launch_blockers.insert(id, status.launch_blocker());
 
+    Ok(status)
+}
+
+async fn get_launch_status(
+    profile: &Profile,
+    cache: &mut ManifestCache,
+    max_age: Duration,
+) -> anyhow::Result<LaunchStatus> {
     let Some(game_directory) = &profile.install.directory else {
         return Ok(LaunchStatus::NeedsGameDir);
     };
 
     if !game_directory.join("FINAL FANTASY XI").exists() {
         tracing::debug!("Missing game directory. Checking for server install URL.");
-        if let Some(download_info) = get_server_game_install(profile).await {
-            return Ok(LaunchStatus::NeedsAndCanInstall { download_info });
-        } else {
-            return Ok(LaunchStatus::NeedsInstall);
+        match get_server_game_install(profile, cache, max_age).await {
+            Ok(Some(download_info)) => {
+                return Ok(LaunchStatus::NeedsAndCanInstall { download_info });
+            }
+            Ok(None) => {
+                return Ok(LaunchStatus::NeedsInstall);
+            }
+            Err(ServerRequestError::Verification(err)) => {
+                return Ok(LaunchStatus::VerificationFailed {
+                    reason: format!("{err:#}"),
+                });
+            }
+            Err(err) => {
+                tracing::warn!("Could not check for a server install: {err}");
+                return Ok(LaunchStatus::NeedsInstall);
+            }
         }
     }
 
@@ -67,147 +176,980 @@ pub async fn check_game_launch(id: u32, state: AppState<'_>) -> anyhow::Result<L
     }
 
     tracing::debug!("Checking if update is needed.");
-    if let Some(versions_info) = needs_update(profile).await {
-        // Cache versions response
-        let server = profile.server.clone().unwrap_or_default();
-        state.update_cache.insert(server, versions_info.clone());
+    let mut update_result = needs_update(profile, cache, max_age).await;
+
+    if profile.offline_policy == OfflinePolicy::Retry {
+        for attempt in 1..=OFFLINE_RETRY_ATTEMPTS {
+            let Err(err) = &update_result else {
+                break;
+            };
+
+            if !err.is_transient() {
+                break;
+            }
+
+            tracing::info!("Retrying update check ({attempt}/{OFFLINE_RETRY_ATTEMPTS}): {err}");
+            tokio::time::sleep(OFFLINE_RETRY_DELAY).await;
+            update_result = needs_update(profile, cache, max_age).await;
+        }
+    }
 
-        return Ok(LaunchStatus::NeedsUpdate { versions_info });
+    match update_result {
+        Ok(Some((versions_info, downgrades))) => {
+            return Ok(LaunchStatus::NeedsUpdate {
+                versions_info,
+                downgrades,
+            });
+        }
+        Ok(None) => {}
+        Err(ServerRequestError::Verification(err)) => {
+            return Ok(LaunchStatus::VerificationFailed {
+                reason: format!("{err:#}"),
+            });
+        }
+        Err(err) => {
+            tracing::warn!("Could not check for updates: {err}");
+
+            let last_known_versions = get_cached_versions_info(profile, cache);
+
+            let installed_versions =
+                get_installed_versions(profile, last_known_versions.as_ref()).await;
+
+            return Ok(LaunchStatus::UpdateCheckFailed {
+                reason: err.to_string(),
+                error: err.details(),
+                policy: profile.offline_policy,
+                last_known_versions,
+                installed_versions,
+                needs_password: needs_password(profile),
+            });
+        }
     }
 
+    if let Some(required) =
+        get_cached_versions_info(profile, cache).and_then(|versions_info| versions_info.client)
+    {
+        let installed = detect_client_version(game_directory);
+        if let Some(mismatch) = required.check(installed.as_ref()) {
+            tracing::warn!("Client version mismatch: {}", mismatch.reason);
+            return Ok(LaunchStatus::ClientVersionMismatch {
+                reason: mismatch.reason,
+                installed,
+                required,
+                can_launch: !mismatch.blocking,
+                needs_password: needs_password(profile),
+            });
+        }
+    }
+
+    if needs_password(profile) {
+        return Ok(LaunchStatus::NeedsPassword);
+    }
+
+    Ok(LaunchStatus::Ready)
+}
+
+/// Returns the last versions manifest fetched from the server, without making any request.
+fn get_cached_versions_info(profile: &Profile, cache: &ManifestCache) -> Option<VersionsInfo> {
+    let url = profile.get_server_info_endpoint("versions").ok()?;
+    let cached = cache.entries.get(&url)?;
+
+    let server_key = signature::get_server_key(profile).ok()?;
+    verify_cached(server_key.as_ref(), cached).ok()?;
+
+    serde_json::from_str(&cached.body).ok()
+}
+
+pub fn needs_password(profile: &Profile) -> bool {
     tracing::debug!("Checking if password input is required.");
-    if !profile.is_retail && !profile.manual_auth {
-        match profile.auth_kind {
-            AuthKind::Token => {
-                if !profile
-                    .get_token_path()
-                    .map(|path| path.exists())
-                    .unwrap_or_default()
-                {
-                    tracing::debug!("Password needed for token authentication.");
-                    return Ok(LaunchStatus::NeedsPassword);
-                }
+    if profile.is_retail || profile.manual_auth {
+        return false;
+    }
+
+    match profile.auth_kind {
+        AuthKind::Token => {
+            if !profile
+                .get_token_path()
+                .map(|path| path.exists())
+                .unwrap_or_default()
+            {
+                tracing::debug!("Password needed for token authentication.");
+                return true;
             }
-            AuthKind::Password => {
-                if profile.password.is_none() {
-                    tracing::debug!("Plaintext password is not set.");
-                    return Ok(LaunchStatus::NeedsPassword);
-                }
+        }
+        AuthKind::Password => {
+            if profile.password.is_none() {
+                tracing::debug!("Plaintext password is not set.");
+                return true;
+            }
+        }
+        AuthKind::ManualPassword => {
+            tracing::debug!("Manual password");
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Reads the installed versions of the components in the manifest,
+/// or of the bootloader and DATs if there is no known manifest.
+async fn get_installed_versions(
+    profile: &Profile,
+    versions_info: Option<&VersionsInfo>,
+) -> BTreeMap<String, String> {
+    let components = match versions_info {
+        Some(versions_info) => versions_info.all_components(),
+        None => VersionsInfo {
+            dats: Some(VersionInfo::default()),
+            bootloader: Some(VersionInfo::default()),
+            components: vec![],
+            client: None,
+        }
+        .all_components(),
+    };
+
+    let mut installed_versions = BTreeMap::new();
+    for component in components {
+        let Ok(version_path) = component.get_version_path(profile) else {
+            continue;
+        };
+
+        if let Ok(version) = fs::read_to_string(version_path).await {
+            installed_versions.insert(component.name, version.trim().to_string());
+        }
+    }
+
+    installed_versions
+}
+
+/// Errors from requesting the server info endpoints.
+#[derive(Debug)]
+pub enum ServerRequestError {
+    /// The server could not be reached.
+    Unavailable(String),
+    /// The server responded with an error status.
+    Status {
+        url: String,
+        status: StatusCode,
+        content_type: Option<String>,
+    },
+    /// The response is not valid JSON of the expected shape.
+    InvalidResponse {
+        url: String,
+        content_type: Option<String>,
+        /// Path of the field that failed to deserialize, such as `components[2].archive`.
+        field: Option<String>,
+        message: String,
+    },
+    /// The response could not be verified against the pinned server key.
+    Verification(anyhow::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Type)]
+pub enum ServerErrorKind {
+    Unavailable,
+    Status,
+    InvalidResponse,
+    Verification,
+}
+
+/// A [ServerRequestError] in a form the frontend can show.
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ServerErrorDetails {
+    pub kind: ServerErrorKind,
+    pub message: String,
+    pub status: Option<u16>,
+    pub content_type: Option<String>,
+    pub field: Option<String>,
+}
+
+impl ServerRequestError {
+    /// Whether the request might succeed when retried later.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ServerRequestError::Unavailable(_) => true,
+            ServerRequestError::Status { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
             }
-            AuthKind::ManualPassword => {
-                tracing::debug!("Manual password");
-                return Ok(LaunchStatus::NeedsPassword);
+            ServerRequestError::InvalidResponse { .. } | ServerRequestError::Verification(_) => {
+                false
             }
         }
     }
 
-    Ok(LaunchStatus::Ready)
+    pub fn details(&self) -> ServerErrorDetails {
+        let (kind, status, content_type, field) = match self {
+            ServerRequestError::Unavailable(_) => (ServerErrorKind::Unavailable, None, None, None),
+            ServerRequestError::Status {
+                status,
+                content_type,
+                ..
+            } => (
+                ServerErrorKind::Status,
+                Some(status.as_u16()),
+                content_type.clone(),
+                None,
+            ),
+            ServerRequestError::InvalidResponse {
+                content_type,
+                field,
+                ..
+            } => (
+                ServerErrorKind::InvalidResponse,
+                None,
+                content_type.clone(),
+                field.clone(),
+            ),
+            ServerRequestError::Verification(_) => {
+                (ServerErrorKind::Verification, None, None, None)
+            }
+        };
+
+        ServerErrorDetails {
+            kind,
+            message: self.to_string(),
+            status,
+            content_type,
+            field,
+        }
+    }
 }
 
-async fn get_server_game_install(profile: &Profile) -> Option<DownloadInfo> {
+impl std::fmt::Display for ServerRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerRequestError::Unavailable(reason) => write!(f, "Server unavailable: {reason}"),
+            ServerRequestError::Status {
+                url,
+                status,
+                content_type,
+            } => {
+                write!(f, "{url} responded with {status}")?;
+                if let Some(content_type) = content_type {
+                    write!(f, " ({content_type})")?;
+                }
+                Ok(())
+            }
+            ServerRequestError::InvalidResponse {
+                url,
+                content_type,
+                field,
+                message,
+            } => {
+                write!(f, "Invalid response from {url}")?;
+                if let Some(field) = field {
+                    write!(f, " at field `{field}`")?;
+                }
+                write!(f, ": {message}")?;
+                if let Some(content_type) = content_type.as_ref().filter(|ct| !ct.contains("json"))
+                {
+                    write!(f, " (served as {content_type} instead of application/json)")?;
+                }
+                Ok(())
+            }
+            ServerRequestError::Verification(err) => write!(f, "Verification failed: {err:#}"),
+        }
+    }
+}
+
+impl std::error::Error for ServerRequestError {}
+
+/// Returns `None` if the profile doesn't connect to a private server, or the server doesn't provide an install.
+async fn get_server_game_install(
+    profile: &Profile,
+    cache: &mut ManifestCache,
+    max_age: Duration,
+) -> Result<Option<DownloadInfo>, ServerRequestError> {
     if profile.is_retail {
-        return None;
+        return Ok(None);
     }
 
     let Some(_server) = &profile.server else {
-        return None;
+        return Ok(None);
     };
 
-    let info_addr = profile.get_server_info_addr();
+    get_signed_json(profile, "install", cache, max_age).await
+}
+
+/// Fetches and parses JSON from the server info address. When the profile pins a server key,
+/// the response has to be signed by it, with the signature available at `<endpoint>.minisig`.
+///
+/// Responses are cached, and reused without any request for `max_age`. After that the server is
+/// asked for changes with a conditional request. Cached responses are verified again before they
+/// are used, since the pinned key may have changed after they were fetched.
+pub async fn get_signed_json<T: DeserializeOwned>(
+    profile: &Profile,
+    endpoint: &str,
+    cache: &mut ManifestCache,
+    max_age: Duration,
+) -> Result<Option<T>, ServerRequestError> {
+    let unavailable = |err: anyhow::Error| ServerRequestError::Unavailable(format!("{err:#}"));
+
+    let url = profile
+        .get_server_info_endpoint(endpoint)
+        .map_err(unavailable)?;
+
+    let server_key =
+        signature::get_server_key(profile).map_err(ServerRequestError::Verification)?;
+
+    if let Some(cached) = cache.entries.get(&url) {
+        if let Err(err) = verify_cached(server_key.as_ref(), cached) {
+            tracing::warn!("Discarding cached {url}: {err:#}");
+            cache.entries.remove(&url);
+        }
+    }
 
-    let response = reqwest::get(format!("http://{info_addr}/install"))
+    if let Some(cached) = cache.get_fresh(&url, max_age) {
+        tracing::debug!("Using cached {url}");
+        return parse_json(&url, None, cached.body.as_bytes()).map(Some);
+    }
+
+    let client = http::server_client(profile).map_err(unavailable)?;
+
+    if !url.starts_with("https://") {
+        tracing::warn!("Fetching {url} over plain HTTP. Its contents could be tampered with.");
+    }
+
+    let mut request = client.get(&url);
+    if let Some(cached) = cache.entries.get(&url) {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|err| ServerRequestError::Unavailable(format!("Could not fetch {url}: {err}")))?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some(cached) = cache.touch(&url) {
+            tracing::debug!("{url} is unchanged");
+            return parse_json(&url, None, cached.body.as_bytes()).map(Some);
+        }
+    }
+
+    // The server doesn't provide this endpoint
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    let header = |name: HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+    };
+    let content_type = header(CONTENT_TYPE);
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+
+    if !response.status().is_success() {
+        return Err(ServerRequestError::Status {
+            url,
+            status: response.status(),
+            content_type,
+        });
+    }
+
+    let body = response
+        .bytes()
         .await
-        .ok()?;
+        .map_err(|err| ServerRequestError::Unavailable(format!("Could not read {url}: {err}")))?;
+
+    let mut signature = None;
+    if let Some(server_key) = server_key {
+        let text = async {
+            client
+                .get(format!("{url}.minisig"))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .with_context(|| format!("Could not fetch signature for {url}"))?
+                .text()
+                .await
+                .with_context(|| format!("Could not read signature for {url}"))
+        }
+        .await
+        .map_err(ServerRequestError::Verification)?;
+
+        signature::verify_bytes(&server_key, &body, &text)
+            .with_context(|| format!("Could not verify {url}"))
+            .map_err(ServerRequestError::Verification)?;
+        signature = Some(text);
+    }
+
+    let parsed = parse_json(&url, content_type, &body)?;
+
+    if let Ok(body) = String::from_utf8(body.to_vec()) {
+        cache.insert(url, body, signature, etag, last_modified);
+    }
 
-    response.json().await.ok()
+    Ok(Some(parsed))
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+/// Checks that a cached response is signed by the currently pinned key, if there is one.
+fn verify_cached(server_key: Option<&PublicKey>, cached: &CachedManifest) -> anyhow::Result<()> {
+    let Some(server_key) = server_key else {
+        return Ok(());
+    };
+
+    let signature = cached
+        .signature
+        .as_ref()
+        .ok_or(anyhow!("It was fetched without a signature."))?;
+
+    signature::verify_bytes(server_key, cached.body.as_bytes(), signature)
+}
+
+/// Parses a server response, keeping track of which field failed to deserialize.
+fn parse_json<T: DeserializeOwned>(
+    url: &str,
+    content_type: Option<String>,
+    body: &[u8],
+) -> Result<T, ServerRequestError> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(body);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let field = err.path().to_string();
+        ServerRequestError::InvalidResponse {
+            url: url.to_string(),
+            content_type,
+            // The path is only "." when the error is in the document itself
+            field: (field != ".").then_some(field),
+            message: err.into_inner().to_string(),
+        }
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Type)]
 pub struct VersionsInfo {
     #[serde(default)]
     pub dats: Option<VersionInfo>,
 
     #[serde(default)]
     pub bootloader: Option<VersionInfo>,
+
+    /// Named components of a v2 manifest, which are installed alongside `dats` and `bootloader`.
+    #[serde(default)]
+    pub components: Vec<ComponentInfo>,
+
+    /// Client version the server expects players to have installed.
+    #[serde(default)]
+    pub client: Option<ClientRequirement>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Type)]
 pub struct VersionInfo {
     pub url: String,
     pub version: String,
+
+    #[serde(default)]
+    pub size: Option<u64>,
+
+    #[serde(default)]
+    pub sha256: Option<String>,
+
+    /// Detached minisign signature of the download.
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Type)]
+pub struct ComponentInfo {
+    pub name: String,
+    pub url: String,
+    pub version: String,
+
+    #[serde(default)]
+    pub size: Option<u64>,
+
+    /// Required, except for the legacy `bootloader` and `dats` entries.
+    #[serde(default)]
+    pub sha256: Option<String>,
+
+    /// Detached minisign signature of the download.
+    #[serde(default)]
+    pub signature: Option<String>,
+
+    #[serde(default)]
+    pub archive: ArchiveKind,
+
+    #[serde(default)]
+    pub base: ComponentBase,
+
+    /// Directory relative to [ComponentInfo::base] that the component is installed into.
+    #[serde(default)]
+    pub target_dir: Option<String>,
+
+    /// Name to store a non-archive download as. Defaults to the last segment of the URL.
+    #[serde(default)]
+    pub file_name: Option<String>,
+
+    /// Removes the target directory before installing the component.
+    #[serde(default)]
+    pub clean: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Type)]
+pub enum ArchiveKind {
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "7z")]
+    SevenZip,
+    #[serde(rename = "zip")]
+    Zip,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Type)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentBase {
+    /// The Ashita or Windower directory, depending on the profile.
+    #[default]
+    Frontend,
+    /// The server specific bootloader directory.
+    Bootloader,
+    /// The server specific pivot DATs directory.
+    Dats,
+    /// The game directory.
+    Game,
+}
+
+impl VersionsInfo {
+    /// All components in the manifest, including the legacy `bootloader` and `dats` entries.
+    pub fn all_components(&self) -> Vec<ComponentInfo> {
+        let mut components = vec![];
+
+        if let Some(info) = &self.bootloader {
+            components.push(ComponentInfo {
+                name: "Bootloader".to_string(),
+                url: info.url.clone(),
+                version: info.version.clone(),
+                size: info.size,
+                sha256: info.sha256.clone(),
+                signature: info.signature.clone(),
+                archive: ArchiveKind::None,
+                base: ComponentBase::Bootloader,
+                target_dir: None,
+                file_name: Some("xiloader.exe".to_string()),
+                clean: false,
+            });
+        }
+
+        if let Some(info) = &self.dats {
+            components.push(ComponentInfo {
+                name: "DATs".to_string(),
+                url: info.url.clone(),
+                version: info.version.clone(),
+                size: info.size,
+                sha256: info.sha256.clone(),
+                signature: info.signature.clone(),
+                archive: ArchiveKind::SevenZip,
+                base: ComponentBase::Dats,
+                target_dir: None,
+                file_name: None,
+                clean: true,
+            });
+        }
+
+        components.extend(self.components.iter().cloned());
+        components
+    }
+
+    /// Index of the first entry in `components` without a sha256, which is required for them.
+    /// Only the legacy `bootloader` and `dats` entries may leave it out.
+    pub fn component_without_sha256(&self) -> Option<usize> {
+        self.components
+            .iter()
+            .position(|component| component.sha256.is_none())
+    }
+}
+
+impl ComponentInfo {
+    pub fn get_base_dir(&self, profile: &Profile) -> anyhow::Result<PathBuf> {
+        Ok(match self.base {
+            ComponentBase::Frontend => {
+                if profile.use_windower {
+                    profile.install.try_get_windower_dir()?
+                } else {
+                    profile.install.try_get_ashita_dir()?
+                }
+            }
+            ComponentBase::Bootloader => profile
+                .get_bootloader_path()
+                .ok_or(anyhow!("Could not determine bootloader path"))?,
+            ComponentBase::Dats => profile
+                .get_pivot_dat_path()
+                .ok_or(anyhow!("Could not determine pivot DAT path"))?,
+            ComponentBase::Game => profile
+                .install
+                .directory
+                .clone()
+                .ok_or(anyhow!("Missing game directory."))?,
+        })
+    }
+
+    pub fn get_target_dir(&self, profile: &Profile) -> anyhow::Result<PathBuf> {
+        let base_dir = self.get_base_dir(profile)?;
+        let Some(target_dir) = &self.target_dir else {
+            return Ok(base_dir);
+        };
+
+        // An empty or `.` target would be the base directory itself, which is only meant for omitted targets
+        let relative = Path::new(target_dir);
+        if target_dir.contains(['\\', ':'])
+            || relative
+                .components()
+                .any(|part| !matches!(part, Component::Normal(_) | Component::CurDir))
+            || !relative
+                .components()
+                .any(|part| matches!(part, Component::Normal(_)))
+        {
+            return Err(anyhow!(
+                "Component '{}' has an invalid target directory: {target_dir}",
+                self.name
+            ));
+        }
+
+        Ok(base_dir.join(relative))
+    }
+
+    /// The legacy bootloader and DATs components keep their version next to their files,
+    /// while other components keep theirs in the server bootloader directory.
+    pub fn get_version_path(&self, profile: &Profile) -> anyhow::Result<PathBuf> {
+        if self.target_dir.is_none()
+            && matches!(self.base, ComponentBase::Bootloader | ComponentBase::Dats)
+        {
+            return Ok(self.get_target_dir(profile)?.join("version.txt"));
+        }
+
+        let sanitized_name: String = self
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+
+        Ok(profile
+            .get_bootloader_path()
+            .ok_or(anyhow!("Could not determine bootloader path"))?
+            .join(format!("versions/{sanitized_name}.txt")))
+    }
+
+    /// Returns the version path, after checking that the component is installed inside its base directory.
+    pub fn get_checked_version_path(&self, profile: &Profile) -> anyhow::Result<PathBuf> {
+        self.get_target_dir(profile)?;
+        if self.archive == ArchiveKind::None {
+            self.get_file_name()?;
+        }
+
+        self.get_version_path(profile)
+    }
+
+    /// The name the component is saved as in its target directory.
+    pub fn get_file_name(&self) -> anyhow::Result<String> {
+        let name = self.file_name.clone().unwrap_or_else(|| {
+            self.url
+                .split(['?', '#'])
+                .next()
+                .and_then(|url| url.rsplit('/').next())
+                .filter(|name| !name.is_empty())
+                .unwrap_or(self.name.as_str())
+                .to_string()
+        });
+
+        // Anything but a single name could point outside of the target directory
+        filename::sanitize(&name)
+            .ok_or_else(|| anyhow!("Component '{}' has an invalid file name: {name}", self.name))
+    }
 }
 
 pub type DownloadInfo = Vec<FileInstallConfig>;
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize, Type)]
+/// Writes JSON Schemas of the `/versions` and `/install` responses, for server operators to validate against.
+pub fn export_manifest_schemas(dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    for (name, schema) in [
+        ("versions", schema_for!(VersionsInfo)),
+        ("install", schema_for!(DownloadInfo)),
+    ] {
+        std::fs::write(
+            dir.join(format!("{name}.schema.json")),
+            serde_json::to_string_pretty(&schema)? + "\n",
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema, Type)]
 pub struct FileInstallConfig {
     pub url: String,
+
+    /// Other URLs serving the exact same file, which are used when `url` fails.
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+
+    /// Expected size in bytes, checked while downloading.
+    #[serde(default)]
+    pub size: Option<u64>,
+
+    /// Expected SHA-256 of the file, checked while downloading and before reusing a downloaded file.
+    #[serde(default)]
+    pub sha256: Option<String>,
+
+    /// Detached minisign signature of the file.
+    #[serde(default)]
+    pub signature: Option<String>,
+
+    /// Position of the file in a split installer archive, starting at 1.
+    /// Without it, parts are ordered by their `.001`, `.002`, ... extensions.
+    #[serde(default)]
+    pub part: Option<u32>,
 }
 
-async fn needs_update(profile: &Profile) -> Option<VersionsInfo> {
-    let Some(versions_info) = get_versions_info(profile).await else {
-        return None;
+/// Returns the versions info if any component needs an update, along with the components that would be downgraded.
+async fn needs_update(
+    profile: &Profile,
+    cache: &mut ManifestCache,
+    max_age: Duration,
+) -> Result<Option<(VersionsInfo, Vec<String>)>, ServerRequestError> {
+    let Some(versions_info) = get_versions_info(profile, cache, max_age).await? else {
+        return Ok(None);
     };
 
-    let bootloader_versions_path = profile.get_bootloader_path()?.join("version.txt");
-    if part_needs_update(&versions_info.bootloader, &bootloader_versions_path)
-        .await
-        .is_some()
-    {
-        return Some(versions_info);
-    }
+    let mut needs_update = false;
+    let mut downgrades = vec![];
+    for component in versions_info.all_components() {
+        // A component that can't be installed safely must not look like it's up to date
+        let version_path = component
+            .get_checked_version_path(profile)
+            .map_err(ServerRequestError::Verification)?;
 
-    let dats_versions_path = profile.get_pivot_dat_path()?.join("version.txt");
-    if part_needs_update(&versions_info.dats, &dats_versions_path)
-        .await
-        .is_some()
-    {
-        return Some(versions_info);
+        match get_component_change(profile, &component, &version_path).await {
+            VersionChange::Unchanged => {}
+            VersionChange::Upgrade => needs_update = true,
+            VersionChange::Downgrade => {
+                needs_update = true;
+                downgrades.push(component.name);
+            }
+        }
     }
 
-    return None;
+    Ok(needs_update.then_some((versions_info, downgrades)))
 }
 
-pub async fn part_needs_update<'a>(
-    version_info: &'a Option<VersionInfo>,
+/// Compares the installed version of a component with the one in the manifest.
+/// Components pinned to another version by the profile, and downgrades the user declined, are treated as unchanged.
+pub async fn get_component_change(
+    profile: &Profile,
+    component: &ComponentInfo,
     version_path: &PathBuf,
-) -> Option<&'a VersionInfo> {
-    if let Some(info) = &version_info {
-        if !version_path.exists() {
-            return Some(&info);
+) -> VersionChange {
+    if let Some(pinned) = profile.pinned_versions.get(&component.name) {
+        if compare_versions(pinned, &component.version) != VersionChange::Unchanged {
+            tracing::debug!(
+                "Ignoring {} version {}, since it is pinned to {pinned}",
+                component.name,
+                component.version
+            );
+            return VersionChange::Unchanged;
         }
+    }
 
-        let Ok(file_version) = fs::read_to_string(version_path).await else {
-            return Some(&info);
-        };
+    if !version_path.exists() {
+        return VersionChange::Upgrade;
+    }
 
-        if file_version != info.version {
-            return Some(&info);
-        }
+    let Ok(file_version) = fs::read_to_string(version_path).await else {
+        return VersionChange::Upgrade;
+    };
+
+    let change = compare_versions(&file_version, &component.version);
+    if change == VersionChange::Downgrade
+        && profile.declined_downgrades.get(&component.name) == Some(&component.version)
+    {
+        tracing::debug!(
+            "Ignoring {} version {}, since its downgrade was declined",
+            component.name,
+            component.version
+        );
+        return VersionChange::Unchanged;
     }
 
-    None
+    change
 }
 
-pub async fn get_versions_info(profile: &Profile) -> Option<VersionsInfo> {
+/// Returns `None` if the profile doesn't connect to a private server, or the server doesn't provide versions.
+pub async fn get_versions_info(
+    profile: &Profile,
+    cache: &mut ManifestCache,
+    max_age: Duration,
+) -> Result<Option<VersionsInfo>, ServerRequestError> {
     if profile.is_retail {
-        return None;
+        return Ok(None);
     }
 
     if profile.server.is_none() {
-        return None;
+        return Ok(None);
     };
 
-    let info_addr = profile.get_server_info_addr();
+    let versions_info: Option<VersionsInfo> =
+        get_signed_json(profile, "versions", cache, max_age).await?;
 
-    let response = reqwest::get(format!("http://{info_addr}/versions"))
-        .await
-        .inspect_err(|err| {
-            tracing::warn!("Could not fetch update information from {info_addr}: {err:?}");
-        })
-        .ok()?;
+    if let Some(index) = versions_info
+        .as_ref()
+        .and_then(|versions_info| versions_info.component_without_sha256())
+    {
+        return Err(ServerRequestError::InvalidResponse {
+            url: profile
+                .get_server_info_endpoint("versions")
+                .unwrap_or_default(),
+            content_type: None,
+            field: Some(format!("components[{index}].sha256")),
+            message: "Components need a sha256, so their downloads can be verified.".to_string(),
+        });
+    }
+
+    Ok(versions_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::profiles::InstallConfig;
+
+    fn component(target_dir: Option<&str>, file_name: Option<&str>) -> ComponentInfo {
+        ComponentInfo {
+            name: "xiloader".to_string(),
+            url: "https://example.com/files/xiloader.exe?v=2".to_string(),
+            version: "1.0.0".to_string(),
+            size: None,
+            sha256: None,
+            signature: None,
+            archive: ArchiveKind::None,
+            base: ComponentBase::Game,
+            target_dir: target_dir.map(str::to_string),
+            file_name: file_name.map(str::to_string),
+            clean: false,
+        }
+    }
+
+    #[test]
+    fn resolves_target_dirs_inside_the_base() {
+        let profile = Profile {
+            install: InstallConfig {
+                directory: Some(PathBuf::from("game")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            component(None, None).get_target_dir(&profile).unwrap(),
+            PathBuf::from("game")
+        );
+        assert_eq!(
+            component(Some("./tools/xiloader"), None)
+                .get_target_dir(&profile)
+                .unwrap(),
+            PathBuf::from("game").join("./tools/xiloader")
+        );
+
+        for target_dir in [
+            "",
+            ".",
+            "./.",
+            "..",
+            "tools/../..",
+            "/tools",
+            "C:\\tools",
+            "..\\tools",
+        ] {
+            assert!(
+                component(Some(target_dir), None)
+                    .get_target_dir(&profile)
+                    .is_err(),
+                "{target_dir}"
+            );
+        }
+    }
+
+    #[test]
+    fn checks_component_paths_before_versions() {
+        let profile = Profile {
+            server: Some("example".to_string()),
+            install: InstallConfig {
+                directory: Some(PathBuf::from("game")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert!(component(Some("tools"), None)
+            .get_checked_version_path(&profile)
+            .is_ok());
+        assert!(component(Some("../tools"), None)
+            .get_checked_version_path(&profile)
+            .is_err());
+        assert!(component(None, Some("../x.exe"))
+            .get_checked_version_path(&profile)
+            .is_err());
+    }
+
+    #[test]
+    fn components_need_a_sha256() {
+        let mut versions_info = VersionsInfo {
+            dats: Some(VersionInfo::default()),
+            bootloader: Some(VersionInfo::default()),
+            components: vec![component(None, None), component(None, None)],
+            client: None,
+        };
+        versions_info.components[0].sha256 = Some("0".repeat(64));
+
+        assert_eq!(versions_info.component_without_sha256(), Some(1));
 
-    let updates_info: VersionsInfo = response.json().await.ok()?;
+        versions_info.components[1].sha256 = Some("0".repeat(64));
+        assert_eq!(versions_info.component_without_sha256(), None);
+    }
+
+    #[test]
+    fn file_names_are_single_names() {
+        assert_eq!(
+            component(None, None).get_file_name().unwrap(),
+            "xiloader.exe"
+        );
+        assert_eq!(
+            component(None, Some("xiloader-v2.exe"))
+                .get_file_name()
+                .unwrap(),
+            "xiloader-v2.exe"
+        );
 
-    Some(updates_info)
+        for file_name in [
+            "",
+            ".",
+            "..",
+            "../../x.exe",
+            "/x.exe",
+            "tools/x.exe",
+            "..\\x.exe",
+            "C:x.exe",
+            "CON",
+            "nul.txt",
+            "xiloader.exe.",
+            "xiloader.exe ",
+        ] {
+            assert!(
+                component(None, Some(file_name)).get_file_name().is_err(),
+                "{file_name}"
+            );
+        }
+    }
 }
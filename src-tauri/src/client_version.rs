@@ -0,0 +1,276 @@
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::version::{compare_versions, VersionChange};
+
+/// Number of ROM directories a client can have, `ROM` through `ROM9`.
+const ROM_DIRECTORY_COUNT: u32 = 9;
+
+/// Signature of the `VS_FIXEDFILEINFO` structure in the version resource of a Windows executable.
+const FIXED_FILE_INFO_SIGNATURE: [u8; 4] = 0xFEEF04BDu32.to_le_bytes();
+
+/// The client version, as far as it can be read from the game files.
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ClientVersion {
+    /// Version from the client's version file, such as `30240404_0`.
+    pub version: Option<String>,
+
+    /// Number of DAT files listed in the VTABLEs of all ROM directories, which grows with every client patch.
+    pub table_entries: u64,
+}
+
+/// Client version a server declares in its versions manifest.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Type)]
+pub struct ClientRequirement {
+    #[serde(default)]
+    pub min_version: Option<String>,
+
+    #[serde(default)]
+    pub exact_version: Option<String>,
+
+    /// Minimum number of VTABLE entries, for clients without a version file.
+    #[serde(default)]
+    pub min_table_entries: Option<u64>,
+
+    /// Blocks launching on a mismatch, instead of only warning about it.
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientMismatch {
+    pub reason: String,
+    /// Whether launching should be blocked. A client version that can't be detected only causes a warning.
+    pub blocking: bool,
+}
+
+/// Reads the installed client version from the `FINAL FANTASY XI` directory in the game directory.
+pub fn detect_client_version(game_directory: &Path) -> Option<ClientVersion> {
+    let client_directory = game_directory.join("FINAL FANTASY XI");
+    if !client_directory.exists() {
+        return None;
+    }
+
+    let version = std::fs::read_to_string(client_directory.join("version.txt"))
+        .ok()
+        .and_then(|content| content.split_whitespace().next().map(|v| v.to_string()));
+
+    let table_entries = (1..=ROM_DIRECTORY_COUNT)
+        .map(|rom| {
+            let vtable_path = if rom == 1 {
+                client_directory.join("VTABLE.DAT")
+            } else {
+                client_directory.join(format!("ROM{rom}/VTABLE{rom}.DAT"))
+            };
+
+            // Each byte is the ROM directory a file ID is stored in, or 0 if it doesn't exist
+            std::fs::read(vtable_path)
+                .map(|vtable| vtable.iter().filter(|rom| **rom != 0).count() as u64)
+                .unwrap_or_default()
+        })
+        .sum();
+
+    Some(ClientVersion {
+        version,
+        table_entries,
+    })
+}
+
+/// Reads the installed Ashita version from the version resource of `Ashita-cli.exe`.
+pub fn detect_ashita_version(ashita_directory: &Path) -> Option<String> {
+    let exe = std::fs::read(ashita_directory.join("Ashita-cli.exe")).ok()?;
+    read_file_version(&exe)
+}
+
+/// Finds the file version of an executable, such as `4.1.0.0`, without walking its resource tree.
+fn read_file_version(exe: &[u8]) -> Option<String> {
+    let start = exe
+        .windows(FIXED_FILE_INFO_SIGNATURE.len())
+        .position(|window| window == FIXED_FILE_INFO_SIGNATURE)?;
+
+    // The signature is followed by the structure version, and then the two halves of the file version
+    let field = |index: usize| {
+        let offset = start + index * 4;
+        exe.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let (high, low) = (field(2)?, field(3)?);
+
+    Some(format!(
+        "{}.{}.{}.{}",
+        high >> 16,
+        high & 0xFFFF,
+        low >> 16,
+        low & 0xFFFF
+    ))
+}
+
+/// Returns why the installed Ashita doesn't satisfy the version a server requires, if it doesn't.
+pub fn check_ashita_version(required: &str, installed: Option<&str>) -> Option<String> {
+    match installed {
+        Some(installed) if compare_versions(installed, required) != VersionChange::Upgrade => None,
+        Some(installed) => Some(format!(
+            "The server requires Ashita {required} or newer, but {installed} is installed."
+        )),
+        None => Some(format!(
+            "The server requires Ashita {required} or newer, but the installed version could not be detected."
+        )),
+    }
+}
+
+impl ClientRequirement {
+    /// Returns why the installed client doesn't satisfy the requirement, if it doesn't.
+    pub fn check(&self, client: Option<&ClientVersion>) -> Option<ClientMismatch> {
+        let version = client.and_then(|client| client.version.as_deref());
+        let mismatch = |reason: String, detected: bool| ClientMismatch {
+            reason,
+            blocking: self.required && detected,
+        };
+
+        if let Some(exact_version) = &self.exact_version {
+            match version {
+                Some(version)
+                    if compare_versions(version, exact_version) == VersionChange::Unchanged => {}
+                Some(version) => {
+                    return Some(mismatch(
+                        format!("The server requires client version {exact_version}, but {version} is installed."),
+                        true,
+                    ));
+                }
+                None => {
+                    return Some(mismatch(
+                        format!("The server requires client version {exact_version}, but the installed version could not be detected."),
+                        false,
+                    ));
+                }
+            }
+        }
+
+        if let Some(min_version) = &self.min_version {
+            match version {
+                Some(version)
+                    if compare_versions(version, min_version) != VersionChange::Upgrade => {}
+                Some(version) => {
+                    return Some(mismatch(
+                        format!("The server requires client version {min_version} or newer, but {version} is installed."),
+                        true,
+                    ));
+                }
+                None => {
+                    return Some(mismatch(
+                        format!("The server requires client version {min_version} or newer, but the installed version could not be detected."),
+                        false,
+                    ));
+                }
+            }
+        }
+
+        if let Some(min_table_entries) = self.min_table_entries {
+            let table_entries = client
+                .map(|client| client.table_entries)
+                .unwrap_or_default();
+
+            if table_entries < min_table_entries {
+                return Some(mismatch(
+                    "The installed client is missing game files. Update the client with PlayOnline, or reinstall it.".to_string(),
+                    client.is_some(),
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(version: &str) -> ClientVersion {
+        ClientVersion {
+            version: Some(version.to_string()),
+            table_entries: 0,
+        }
+    }
+
+    #[test]
+    fn checks_the_minimum_version() {
+        let requirement = ClientRequirement {
+            min_version: Some("30240404_0".to_string()),
+            required: true,
+            ..Default::default()
+        };
+
+        assert!(requirement.check(Some(&client("30240404_0"))).is_none());
+        assert!(requirement.check(Some(&client("30241101_1"))).is_none());
+
+        let mismatch = requirement.check(Some(&client("30231101_0"))).unwrap();
+        assert!(mismatch.blocking);
+    }
+
+    #[test]
+    fn checks_the_exact_version() {
+        let requirement = ClientRequirement {
+            exact_version: Some("30240404_0".to_string()),
+            ..Default::default()
+        };
+
+        assert!(requirement.check(Some(&client("30240404_0"))).is_none());
+
+        // Without `required`, a mismatch is only a warning
+        let mismatch = requirement.check(Some(&client("30241101_1"))).unwrap();
+        assert!(!mismatch.blocking);
+
+        let required = ClientRequirement {
+            required: true,
+            ..requirement
+        };
+        assert!(
+            required
+                .check(Some(&client("30241101_1")))
+                .unwrap()
+                .blocking
+        );
+    }
+
+    #[test]
+    fn only_warns_without_a_version_file() {
+        let dir = std::env::temp_dir().join("xi-launcher-client-version-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("FINAL FANTASY XI")).unwrap();
+
+        let installed = detect_client_version(&dir).unwrap();
+        assert_eq!(installed.version, None);
+
+        let requirement = ClientRequirement {
+            min_version: Some("30240404_0".to_string()),
+            required: true,
+            ..Default::default()
+        };
+        let mismatch = requirement.check(Some(&installed)).unwrap();
+        assert!(!mismatch.blocking);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reads_the_ashita_version() {
+        let mut exe = b"MZ padding".to_vec();
+        exe.extend(FIXED_FILE_INFO_SIGNATURE);
+        exe.extend(0x0001_0000u32.to_le_bytes());
+        exe.extend(0x0004_0001u32.to_le_bytes());
+        exe.extend(0x0002_0003u32.to_le_bytes());
+
+        let version = read_file_version(&exe).unwrap();
+        assert_eq!(version, "4.1.2.3");
+        assert!(read_file_version(b"MZ padding").is_none());
+
+        assert!(check_ashita_version("4.1", Some(&version)).is_none());
+        assert!(check_ashita_version("4.1.2.3", Some(&version)).is_none());
+        assert!(check_ashita_version("4.2", Some(&version)).is_some());
+        assert!(check_ashita_version("4.1", None).is_some());
+    }
+}
@@ -3,17 +3,30 @@ use std::{path::PathBuf, process::Command};
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use tauri::{ipc::Channel, AppHandle};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     ashita,
+    bandwidth::{self, BandwidthSettings},
     check_game::{check_game_launch, get_versions_info, DownloadInfo, LaunchStatus},
-    config::profiles::{AuthKind, Profile, Profiles},
+    config::{
+        launcher,
+        profiles::{AuthKind, Profile, Profiles},
+    },
+    convert::{self, ConversionReport},
+    diagnose::{self, ServerDiagnosis},
+    download_cache::PruneReport,
+    http::{self, HttpSettings},
+    news::{self, ServerNews},
+    readiness::{self, ReadinessReport},
+    server_directory::{self, ServerDirectoryEntry, ServerDirectoryFeed},
+    server_status::{self, ServerStatus},
     state::AppState,
     tasks::{
         install::{install_client, InstallTaskProgress},
         update::{update_with_versions, UpdateTaskMessage},
     },
-    windower,
+    util, windower,
 };
 
 #[tauri::command]
@@ -45,6 +58,7 @@ pub async fn save_profile(
         };
 
         *existing = profile;
+        state.launch_blockers.remove(&existing_id);
     } else {
         tracing::info!("Creating new profile");
         state.profiles.add_new_profile(profile);
@@ -119,6 +133,7 @@ pub async fn delete_profile(id: u32, state: AppState<'_>) -> Result<(), String>
     let mut state = state.write().await;
 
     if let Some(profile) = state.profiles.map.remove(&id) {
+        state.launch_blockers.remove(&id);
         state
             .profiles
             .ids
@@ -138,6 +153,214 @@ pub async fn delete_profile(id: u32, state: AppState<'_>) -> Result<(), String>
     Ok(())
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn convert_profile(
+    id: u32,
+    to_windower: bool,
+    state: AppState<'_>,
+    app_handle: AppHandle,
+) -> Result<ConversionReport, String> {
+    let mut state = state.write().await;
+
+    let mut profile = state
+        .profiles
+        .map
+        .get(&id)
+        .cloned()
+        .ok_or("No profile with the given ID found.".to_string())?;
+
+    let previous = profile.clone();
+    let mut report = convert::convert_profile(&mut profile, to_windower)
+        .map_err(|err| format!("Could not convert profile: {err:?}"))?;
+
+    if !profile.use_windower {
+        ashita::update_ashita_files(&profile, &app_handle)
+            .await
+            .map_err(|err| format!("Could not update Ashita files: {err:?}"))?;
+    }
+
+    // Files are only changed once nothing else can fail, and changed back if the profile can't be saved
+    convert::move_server_files(&previous, &profile, &mut report)
+        .map_err(|err| format!("Could not convert profile: {err:?}"))?;
+
+    let settings_backup = match convert::write_windower_settings(&profile) {
+        Ok(backup) => backup,
+        Err(err) => {
+            convert::undo_moves(&previous, &profile, &mut report);
+            return Err(format!("Could not convert profile: {err:?}"));
+        }
+    };
+
+    state.profiles.map.insert(id, profile.clone());
+    state.launch_blockers.remove(&id);
+
+    if let Err(err) = state.save_configs() {
+        if let Some(backup) = settings_backup {
+            backup.restore();
+        }
+        convert::undo_moves(&previous, &profile, &mut report);
+        state.profiles.map.insert(id, previous);
+        return Err(format!("Couldn't save configs to disk: {err:?}"));
+    }
+
+    Ok(report)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_server_list_urls(state: AppState<'_>) -> Result<Vec<String>, String> {
+    Ok(state.read().await.config.server_list_urls.clone())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn save_server_list_urls(urls: Vec<String>, state: AppState<'_>) -> Result<(), String> {
+    let mut state = state.write().await;
+
+    state.config.server_list_urls = urls
+        .into_iter()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+
+    state
+        .save_configs()
+        .map_err(|err| format!("Couldn't save configs to disk: {err:?}"))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_bandwidth_settings(state: AppState<'_>) -> Result<BandwidthSettings, String> {
+    Ok(state.read().await.config.bandwidth.clone())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn save_bandwidth_settings(
+    settings: BandwidthSettings,
+    state: AppState<'_>,
+) -> Result<(), String> {
+    if let Some(schedule) = &settings.unlimited_hours {
+        if schedule.start_hour > 23 || schedule.end_hour > 23 {
+            return Err("Schedule hours must be between 0 and 23.".to_string());
+        }
+    }
+
+    // Applies to downloads that are already running as well. Those can hold the state lock
+    // for a long time, so the limit is changed before waiting for it.
+    bandwidth::limiter().configure(&settings);
+
+    let mut state = state.write().await;
+    state.config.bandwidth = settings;
+
+    state
+        .save_configs()
+        .map_err(|err| format!("Couldn't save configs to disk: {err:?}"))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_http_settings(state: AppState<'_>) -> Result<HttpSettings, String> {
+    Ok(state.read().await.config.http.clone())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn save_http_settings(settings: HttpSettings, state: AppState<'_>) -> Result<(), String> {
+    let mut state = state.write().await;
+
+    // Requests that already started keep using the previous client
+    http::configure(&settings).map_err(|err| format!("Invalid HTTP settings: {err:#}"))?;
+    state.config.http = settings;
+
+    state
+        .save_configs()
+        .map_err(|err| format!("Couldn't save configs to disk: {err:?}"))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_server_directory(state: AppState<'_>) -> Result<Vec<ServerDirectoryFeed>, String> {
+    let read_state = state.read().await;
+    let local_data_dir = read_state.local_data_dir.clone();
+    let urls = read_state.config.server_list_urls.clone();
+    drop(read_state);
+
+    server_directory::get_server_directory(&local_data_dir, &urls)
+        .await
+        .map_err(|err| format!("Failed to get server directory: {err:?}"))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn create_profile_from_directory(
+    entry: ServerDirectoryEntry,
+    template_id: Option<u32>,
+    state: AppState<'_>,
+) -> Result<u32, String> {
+    let mut state = state.write().await;
+
+    let template = match template_id {
+        Some(template_id) => state
+            .profiles
+            .map
+            .get(&template_id)
+            .cloned()
+            .ok_or(format!("No profile found with ID {}", template_id))?,
+        None => Profile {
+            start_pos_x: -1,
+            start_pos_y: -1,
+            ..Default::default()
+        },
+    };
+
+    tracing::info!("Creating new profile for server {}", entry.name);
+    let id = state.profiles.add_new_profile(entry.to_profile(template));
+
+    state
+        .save_configs()
+        .map_err(|err| format!("Couldn't save profile to disk: {err:?}"))?;
+
+    Ok(id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn pin_component_version(
+    id: u32,
+    component: String,
+    version: Option<String>,
+    state: AppState<'_>,
+) -> Result<(), String> {
+    let mut state = state.write().await;
+
+    let Some(profile) = state.profiles.map.get_mut(&id) else {
+        return Err(format!("No profile found with ID {}", id));
+    };
+
+    if let Some(version) = version {
+        tracing::info!("Pinning {component} to version {version} for profile {id}");
+        profile.pinned_versions.insert(component, version);
+    } else {
+        tracing::info!("Releasing pin of {component} for profile {id}");
+        profile.pinned_versions.remove(&component);
+    }
+    state.launch_blockers.remove(&id);
+
+    state
+        .save_configs()
+        .map_err(|err| format!("Couldn't save configs to disk: {err:?}"))?;
+
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize, Type)]
 pub enum StartProfileResult {
     Started,
@@ -187,43 +410,171 @@ pub async fn check_launch_profile(id: u32, state: AppState<'_>) -> Result<Launch
 
 #[tauri::command]
 #[specta::specta]
-pub async fn update_profile_server_files(
-    id: u32,
+pub async fn get_server_status(
     state: AppState<'_>,
-    channel: Channel<UpdateTaskMessage>,
+    channel: Channel<ServerStatus>,
 ) -> Result<(), String> {
     let mut state = state.write().await;
 
+    // Only keep a single refresh running
+    if let Some(token) = state.server_status_task.take() {
+        token.cancel();
+    }
+
+    let token = CancellationToken::new();
+    state.server_status_task = Some(token.clone());
+
+    let profiles = state
+        .profiles
+        .ids
+        .iter()
+        .filter_map(|id| state.profiles.map.get(id).cloned())
+        .collect();
+
+    tauri::async_runtime::spawn(server_status::refresh_server_statuses(
+        profiles, channel, token,
+    ));
+
+    Ok(())
+}
+
+/// Checks the server info endpoints of the profile's server, and reports everything wrong with them.
+#[tauri::command]
+#[specta::specta]
+pub async fn diagnose_server(id: u32, state: AppState<'_>) -> Result<ServerDiagnosis, String> {
     let profile = state
+        .read()
+        .await
         .profiles
         .map
         .get(&id)
+        .cloned()
         .ok_or("No profile with the given ID found.".to_string())?;
 
-    let Some(server) = profile.server.clone() else {
-        return Err("Expected server to have a name.".to_string());
-    };
+    Ok(diagnose::diagnose_server(&profile).await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_server_news(id: u32, state: AppState<'_>) -> Result<ServerNews, String> {
+    let read_state = state.read().await;
 
-    let mut versions_info = state.update_cache.remove(&server);
+    let profile = read_state
+        .profiles
+        .map
+        .get(&id)
+        .ok_or("No profile with the given ID found.".to_string())?;
 
-    // Re-get profile after having updated cache
-    let profile = state
+    if profile.is_retail || profile.server.is_none() {
+        return Err("Only private servers can provide news.".to_string());
+    }
+
+    news::get_server_news(&read_state.local_data_dir, profile)
+        .await
+        .map_err(|err| format!("Failed to get server news: {err:?}"))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn mark_server_news_read(
+    id: u32,
+    item_ids: Vec<String>,
+    state: AppState<'_>,
+) -> Result<ServerNews, String> {
+    let read_state = state.read().await;
+
+    let profile = read_state
         .profiles
         .map
         .get(&id)
         .ok_or("No profile with the given ID found.".to_string())?;
 
-    if versions_info.is_none() {
-        versions_info = get_versions_info(profile).await;
+    news::mark_news_read(&read_state.local_data_dir, profile, item_ids)
+        .map_err(|err| format!("Failed to mark server news as read: {err:?}"))
+}
+
+/// Checks the launch status of the profile, asking the server for its manifests even if the cached ones are fresh.
+#[tauri::command]
+#[specta::specta]
+pub async fn refresh_launch_profile(id: u32, state: AppState<'_>) -> Result<LaunchStatus, String> {
+    {
+        let mut state = state.write().await;
+
+        let profile = state
+            .profiles
+            .map
+            .get(&id)
+            .ok_or("No profile with the given ID found.".to_string())?;
+
+        if let Ok(info_url) = profile.get_server_info_url() {
+            state.manifest_cache.mark_stale(info_url.as_str());
+        }
     }
 
-    match versions_info {
-        Some(info) => {
-            update_with_versions(profile, info, channel)
-                .await
-                .map_err(|err| format!("Failed to update game: {err:?}"))?;
+    check_launch_profile(id, state).await
+}
+
+/// Runs all launch checks for the profile, and reports every problem found along with how to fix it.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_readiness_report(id: u32, state: AppState<'_>) -> Result<ReadinessReport, String> {
+    readiness::get_readiness_report(id, state)
+        .await
+        .map_err(|err| format!("Failed to check profile readiness: {err:?}"))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn update_profile_server_files(
+    id: u32,
+    allow_downgrade: bool,
+    state: AppState<'_>,
+    channel: Channel<UpdateTaskMessage>,
+) -> Result<(), String> {
+    let mut state_guard = state.write().await;
+    let state = &mut *state_guard;
+    let max_age = state.config.get_manifest_cache_ttl();
+
+    let profile = state
+        .profiles
+        .map
+        .get(&id)
+        .ok_or("No profile with the given ID found.".to_string())?;
+
+    if profile.server.is_none() {
+        return Err("Expected server to have a name.".to_string());
+    };
+
+    // Usually served from the cache, since the launch check just fetched it
+    let versions_info = get_versions_info(profile, &mut state.manifest_cache, max_age)
+        .await
+        .map_err(|err| format!("Failed to get update information: {err}"))?;
+
+    let Some(info) = versions_info else {
+        return Ok(());
+    };
+
+    let declined_downgrades = update_with_versions(
+        profile,
+        info,
+        allow_downgrade,
+        &state.download_cache,
+        channel,
+    )
+    .await
+    .map_err(|err| format!("Failed to update game: {err:?}"))?;
+    state.launch_blockers.remove(&id);
+
+    if !declined_downgrades.is_empty() {
+        // Remember the declined versions, so the same downgrade isn't offered at every launch
+        tracing::info!("Declined downgrades for profile {id}: {declined_downgrades:?}");
+        if let Some(profile) = state.profiles.map.get_mut(&id) {
+            profile.declined_downgrades.extend(declined_downgrades);
         }
-        None => {}
+
+        state
+            .save_configs()
+            .map_err(|err| format!("Couldn't save configs to disk: {err:?}"))?;
     }
 
     Ok(())
@@ -237,6 +588,19 @@ pub async fn launch_profile(
     state: AppState<'_>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
+    // The launch page checks first, but nothing else should be able to skip a failed check
+    let recorded_blocker = state.read().await.launch_blockers.get(&id).cloned();
+    let blocker = match recorded_blocker {
+        Some(blocker) => blocker,
+        None => check_game_launch(id, state.clone())
+            .await
+            .map_err(|err| format!("Failed to check launch of game: {err:?}"))?
+            .launch_blocker(),
+    };
+    if let Some(blocker) = blocker {
+        return Err(format!("Can't launch the game. {blocker}"));
+    }
+
     let read_state = state.read().await;
 
     let profile = read_state
@@ -258,15 +622,18 @@ pub async fn launch_profile(
     Ok(())
 }
 
+/// Installs the game into the profile's install directory. With a `local_source`, the game is
+/// installed from installer parts or an installed client on this computer instead of downloading it.
 #[tauri::command]
 #[specta::specta]
 pub async fn install_game_for_profile(
     id: u32,
     download_info: DownloadInfo,
+    local_source: Option<PathBuf>,
     state: AppState<'_>,
     channel: Channel<InstallTaskProgress>,
 ) -> Result<(), String> {
-    install_client(id, download_info, state, channel)
+    install_client(id, download_info, local_source, state, channel)
         .await
         .map_err(|err| format!("{err:?}"))
 }
@@ -281,51 +648,39 @@ pub async fn cancel_possible_profile_task(id: u32, state: AppState<'_>) -> Resul
     Ok(())
 }
 
+/// Shrinks the shared download cache to `max_mib`, or to the configured limit. 0 empties it.
 #[tauri::command]
 #[specta::specta]
-pub async fn list_ashita_addons(ashita_directory: PathBuf) -> Result<Vec<String>, String> {
-    let entries = std::fs::read_dir(ashita_directory.join("addons"))
-        .map_err(|err| format!("Couldn't find Ashita addons: {err:?}"))?;
-
-    let mut addons = vec![];
-    for entry in entries {
-        entry
-            .ok()
-            .filter(|e| e.file_type().map(|e| e.is_dir()).unwrap_or_default())
-            .and_then(|e| e.file_name().into_string().ok())
-            .map(|name| addons.push(name));
-    }
+pub async fn prune_download_cache(
+    max_mib: Option<u64>,
+    state: AppState<'_>,
+) -> Result<PruneReport, String> {
+    let read_state = state.read().await;
+    let max_bytes = match max_mib {
+        Some(max_mib) => launcher::mib_to_bytes(max_mib),
+        None => read_state.config.get_download_cache_limit(),
+    };
+    let cache = read_state.download_cache.clone();
+    drop(read_state);
+
+    tokio::task::spawn_blocking(move || cache.prune(max_bytes))
+        .await
+        .map_err(|err| format!("{err:?}"))?
+        .map_err(|err| format!("Failed to prune download cache: {err:?}"))
+}
 
-    Ok(addons)
+#[tauri::command]
+#[specta::specta]
+pub async fn list_ashita_addons(ashita_directory: PathBuf) -> Result<Vec<String>, String> {
+    util::list_addons(&ashita_directory)
+        .map_err(|err| format!("Couldn't find Ashita addons: {err:?}"))
 }
 
 #[tauri::command]
 #[specta::specta]
 pub async fn list_ashita_plugins(ashita_directory: PathBuf) -> Result<Vec<String>, String> {
-    let entries = std::fs::read_dir(ashita_directory.join("plugins"))
-        .map_err(|err| format!("Couldn't find Ashita plugins: {err:?}"))?;
-
-    let mut plugins = vec![];
-    for entry in entries {
-        entry
-            .ok()
-            .filter(|e| e.file_type().map(|e| e.is_file()).unwrap_or_default())
-            .filter(|e| {
-                e.path()
-                    .extension()
-                    .map(|ext| ext == "dll")
-                    .unwrap_or_default()
-            })
-            .and_then(|e| {
-                e.path()
-                    .file_stem()
-                    .and_then(|os_str| os_str.to_str())
-                    .map(|s| s.to_owned())
-            })
-            .map(|name| plugins.push(name.to_owned()));
-    }
-
-    Ok(plugins)
+    util::list_plugins(&ashita_directory)
+        .map_err(|err| format!("Couldn't find Ashita plugins: {err:?}"))
 }
 
 #[tauri::command]
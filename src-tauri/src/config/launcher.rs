@@ -1,17 +1,73 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use serde::{Deserialize, Serialize};
 
+use crate::{bandwidth::BandwidthSettings, http::HttpSettings};
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct LauncherConfig {
     #[serde(default)]
     pub install_dir: Option<PathBuf>,
+
+    /// URLs serving a [crate::server_directory::ServerDirectory] to create profiles from.
+    #[serde(default)]
+    pub server_list_urls: Vec<String>,
+
+    /// How long server manifests are reused before asking the server for changes again.
+    #[serde(default)]
+    pub manifest_cache_ttl_secs: Option<u64>,
+
+    /// How many files are downloaded at the same time.
+    #[serde(default)]
+    pub max_concurrent_downloads: Option<u32>,
+
+    #[serde(default)]
+    pub bandwidth: BandwidthSettings,
+
+    /// Size limit of the download cache shared by all profiles in MiB. 0 disables the cache.
+    #[serde(default)]
+    pub download_cache_limit_mib: Option<u64>,
+
+    /// Proxy, timeouts and certificates for all HTTP requests.
+    #[serde(default)]
+    pub http: HttpSettings,
 }
 
 pub const LAUNCHER_CONFIG_FILENAME: &'static str = "config.json";
 
+pub const DEFAULT_MANIFEST_CACHE_TTL_SECS: u64 = 5 * 60;
+
+pub const DEFAULT_MAX_CONCURRENT_DOWNLOADS: u32 = 3;
+
+pub const DEFAULT_DOWNLOAD_CACHE_LIMIT_MIB: u64 = 16 * 1024;
+
+/// Converts a size in MiB to bytes. Sizes too large to fit are capped, instead of wrapping around to a small limit.
+pub fn mib_to_bytes(mib: u64) -> u64 {
+    mib.saturating_mul(1024 * 1024)
+}
+
 impl LauncherConfig {
     pub fn get_path(dir: &PathBuf) -> PathBuf {
         dir.join(LAUNCHER_CONFIG_FILENAME)
     }
+
+    pub fn get_manifest_cache_ttl(&self) -> Duration {
+        Duration::from_secs(
+            self.manifest_cache_ttl_secs
+                .unwrap_or(DEFAULT_MANIFEST_CACHE_TTL_SECS),
+        )
+    }
+
+    pub fn get_max_concurrent_downloads(&self) -> usize {
+        self.max_concurrent_downloads
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_DOWNLOADS)
+            .max(1) as usize
+    }
+
+    pub fn get_download_cache_limit(&self) -> u64 {
+        mib_to_bytes(
+            self.download_cache_limit_mib
+                .unwrap_or(DEFAULT_DOWNLOAD_CACHE_LIMIT_MIB),
+        )
+    }
 }
@@ -1,7 +1,9 @@
 use std::{collections::BTreeMap, path::PathBuf};
 
 use super::serde_util;
+use anyhow::{anyhow, Context};
 use regex::Regex;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
@@ -30,6 +32,18 @@ pub struct Profile {
     #[serde(default)]
     pub server_info_addr: Option<String>,
 
+    /// Minisign public key that server manifests and downloads must be signed with.
+    #[serde(default)]
+    pub server_public_key: Option<String>,
+
+    /// PEM file with CA certificates to trust for a self-hosted HTTPS server info address.
+    #[serde(default)]
+    pub server_ca_certificate: Option<PathBuf>,
+
+    /// Ashita version the server requires, from the server directory entry the profile was created from.
+    #[serde(default)]
+    pub required_ashita_version: Option<String>,
+
     #[serde(skip_serializing_if = "serde_util::is_false")]
     #[serde(default)]
     pub use_windower: bool,
@@ -95,6 +109,21 @@ pub struct Profile {
 
     #[serde(default)]
     pub windower_profile: Option<String>,
+
+    /// What to do when the server can't be asked for updates.
+    #[serde(skip_serializing_if = "serde_util::is_default")]
+    #[serde(default)]
+    pub offline_policy: OfflinePolicy,
+
+    /// Server components pinned to a version, which won't be updated to any other version.
+    #[serde(skip_serializing_if = "serde_util::map_is_empty")]
+    #[serde(default)]
+    pub pinned_versions: BTreeMap<String, String>,
+
+    /// Older server versions of components the user chose to keep their installed version over.
+    #[serde(skip_serializing_if = "serde_util::map_is_empty")]
+    #[serde(default)]
+    pub declined_downgrades: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize, Type)]
@@ -106,6 +135,17 @@ pub enum AuthKind {
     ManualPassword = 2,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, Type)]
+pub enum OfflinePolicy {
+    /// Allow launching with the files that are already installed.
+    #[default]
+    LaunchAnyway,
+    /// Don't allow launching until the server can be reached.
+    Block,
+    /// Retry the update check a few times before giving up.
+    Retry,
+}
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize, Type)]
 pub struct InstallConfig {
     #[serde(default)]
@@ -163,11 +203,12 @@ impl Profiles {
         dir.join(PROFILES_CONFIG_FILENAME)
     }
 
-    pub fn add_new_profile(&mut self, mut profile: Profile) {
+    pub fn add_new_profile(&mut self, mut profile: Profile) -> u32 {
         let new_id = self.ids.iter().copied().max().unwrap_or(1) + 1;
         self.ids.push(new_id);
         profile.id = new_id;
         self.map.insert(new_id, profile);
+        new_id
     }
 }
 
@@ -234,4 +275,29 @@ impl Profile {
             )
         })
     }
+
+    /// Base URL of the server info endpoints. Addresses without a scheme use plain HTTP.
+    pub fn get_server_info_url(&self) -> anyhow::Result<Url> {
+        let info_addr = self.get_server_info_addr();
+        let url = if info_addr.contains("://") {
+            Url::parse(&info_addr)
+        } else {
+            Url::parse(&format!("http://{info_addr}"))
+        }
+        .with_context(|| format!("Invalid server info address: {info_addr}"))?;
+
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!(
+                "Unsupported scheme for server info address: {info_addr}"
+            ));
+        }
+
+        Ok(url)
+    }
+
+    /// URL of an endpoint relative to the server info address, keeping any path prefix.
+    pub fn get_server_info_endpoint(&self, endpoint: &str) -> anyhow::Result<String> {
+        let url = self.get_server_info_url()?;
+        Ok(format!("{}/{endpoint}", url.as_str().trim_end_matches('/')))
+    }
 }
@@ -1,5 +1,7 @@
 #![allow(unused)]
 
+use std::collections::BTreeMap;
+
 pub const fn default_true() -> bool {
     true
 }
@@ -20,6 +22,10 @@ pub fn vec_is_empty<T>(value: &Vec<T>) -> bool {
     value.is_empty()
 }
 
+pub fn map_is_empty<K, V>(value: &BTreeMap<K, V>) -> bool {
+    value.is_empty()
+}
+
 pub const fn default_minus_one() -> i32 {
     -1
 }
@@ -0,0 +1,306 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::anyhow;
+use serde::Serialize;
+use specta::Type;
+
+use crate::{config::profiles::Profile, util, windower};
+
+#[derive(Debug, Clone, Default, Serialize, Type)]
+pub struct ConversionReport {
+    pub moved_bootloader: bool,
+    pub moved_dats: bool,
+
+    /// Enabled addons that were not found in the target frontend.
+    pub unmatched_addons: Vec<String>,
+
+    /// Enabled plugins that were not found in the target frontend.
+    pub unmatched_plugins: Vec<String>,
+
+    /// Extra pivots that were not found in the DATs folder of the target frontend.
+    pub unmatched_pivots: Vec<String>,
+}
+
+/// Switches the profile between Windower and Ashita, carrying over settings shared between the two.
+/// Nothing is written to disk yet: the caller regenerates the Ashita files, then calls
+/// [`move_server_files`] and [`write_windower_settings`] once every other step has succeeded.
+pub fn convert_profile(
+    profile: &mut Profile,
+    to_windower: bool,
+) -> anyhow::Result<ConversionReport> {
+    if profile.use_windower == to_windower {
+        return Err(anyhow!(
+            "Profile is already using {}.",
+            if to_windower { "Windower" } else { "Ashita" }
+        ));
+    }
+
+    let target_dir = if to_windower {
+        profile.install.try_get_windower_dir()?
+    } else {
+        profile.install.try_get_ashita_dir()?
+    };
+
+    if !target_dir.exists() {
+        return Err(anyhow!(
+            "Could not find the directory to convert to: {}",
+            target_dir.display()
+        ));
+    }
+
+    let previous = profile.clone();
+    profile.use_windower = to_windower;
+
+    let mut report = ConversionReport::default();
+
+    tracing::info!(
+        "Converting profile {} to {}",
+        profile.id,
+        if to_windower { "Windower" } else { "Ashita" }
+    );
+
+    if to_windower {
+        profile
+            .windower_profile
+            .get_or_insert_with(|| previous.get_profile_filename());
+    } else if let Some(windower_profile) = &previous.windower_profile {
+        match windower::get_windower_profile_resolution(&previous, windower_profile) {
+            Ok(Some(resolution)) => profile.resolution = resolution,
+            Ok(None) => {}
+            Err(err) => tracing::warn!("Could not read Windower profile resolution: {err:?}"),
+        }
+    }
+
+    // Addons and plugins share the same folder layout in both Windower and Ashita
+    let available_addons = util::list_addons(&target_dir).unwrap_or_default();
+    report.unmatched_addons = unmatched(&profile.enabled_addons, &available_addons);
+
+    let available_plugins = util::list_plugins(&target_dir).unwrap_or_default();
+    report.unmatched_plugins = unmatched(&profile.enabled_plugins, &available_plugins);
+
+    if let Some(dats_root) = profile
+        .get_pivot_dat_path()
+        .and_then(|path| path.parent().map(|parent| parent.to_path_buf()))
+    {
+        report.unmatched_pivots = profile
+            .extra_pivots
+            .iter()
+            .filter(|pivot| !dats_root.join(pivot).exists())
+            .cloned()
+            .collect();
+    }
+
+    Ok(report)
+}
+
+/// Windower's settings.xml as it was before [`write_windower_settings`] changed it.
+pub struct SettingsBackup {
+    path: PathBuf,
+    contents: Vec<u8>,
+}
+
+impl SettingsBackup {
+    /// Writes the previous settings back, for when the conversion fails after changing them.
+    pub fn restore(self) {
+        if let Err(err) = fs::write(&self.path, &self.contents) {
+            tracing::error!("Could not restore {}: {err:?}", self.path.display());
+        }
+    }
+}
+
+/// Creates the profile's Windower profile in settings.xml, if it was converted to Windower.
+/// Returns a backup of the previous settings, so they can be restored if a later step fails.
+pub fn write_windower_settings(profile: &Profile) -> anyhow::Result<Option<SettingsBackup>> {
+    let (true, Some(windower_profile)) = (profile.use_windower, &profile.windower_profile) else {
+        return Ok(None);
+    };
+
+    let path = profile.install.try_get_windower_dir()?.join("settings.xml");
+    let backup = SettingsBackup {
+        contents: fs::read(&path)
+            .map_err(|err| anyhow!("Could not read {}: {err:?}", path.display()))?,
+        path,
+    };
+
+    if let Err(err) = windower::sync_windower_profile(profile, windower_profile) {
+        backup.restore();
+        return Err(err);
+    }
+
+    // Server args are written again on launch, so an incomplete profile shouldn't block conversion
+    if let Err(err) = windower::update_windower_profile(profile, windower_profile, None) {
+        tracing::warn!("Could not write server args to Windower profile yet: {err:?}");
+    }
+
+    Ok(Some(backup))
+}
+
+/// Moves the bootloader and server DATs into the converted profile's layout.
+/// If one of them can't be moved, the other is moved back so the files match `previous` again.
+pub fn move_server_files(
+    previous: &Profile,
+    profile: &Profile,
+    report: &mut ConversionReport,
+) -> anyhow::Result<()> {
+    report.moved_bootloader = move_server_dir(
+        previous.get_bootloader_path(),
+        profile.get_bootloader_path(),
+    )?;
+
+    match move_server_dir(previous.get_pivot_dat_path(), profile.get_pivot_dat_path()) {
+        Ok(moved) => report.moved_dats = moved,
+        Err(err) => {
+            undo_moves(previous, profile, report);
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the server files back to where `previous` expects them, for when the conversion fails after moving them.
+pub fn undo_moves(previous: &Profile, profile: &Profile, report: &mut ConversionReport) {
+    if report.moved_dats {
+        match move_server_dir(profile.get_pivot_dat_path(), previous.get_pivot_dat_path()) {
+            Ok(_) => report.moved_dats = false,
+            Err(err) => tracing::error!("Could not move server DATs back: {err:?}"),
+        }
+    }
+
+    if report.moved_bootloader {
+        match move_server_dir(
+            profile.get_bootloader_path(),
+            previous.get_bootloader_path(),
+        ) {
+            Ok(_) => report.moved_bootloader = false,
+            Err(err) => tracing::error!("Could not move bootloader back: {err:?}"),
+        }
+    }
+}
+
+/// Moves a per-server directory to its new location, unless the target is already populated.
+fn move_server_dir(from: Option<PathBuf>, to: Option<PathBuf>) -> anyhow::Result<bool> {
+    let (Some(from), Some(to)) = (from, to) else {
+        return Ok(false);
+    };
+
+    if !from.exists() || to.exists() {
+        return Ok(false);
+    }
+
+    tracing::info!("Moving {} to {}", from.display(), to.display());
+    util::move_dir_all(&from, &to).map_err(|err| {
+        anyhow!(
+            "Could not move {} to {}: {err:?}",
+            from.display(),
+            to.display()
+        )
+    })?;
+
+    Ok(true)
+}
+
+fn unmatched(enabled: &Option<Vec<String>>, available: &[String]) -> Vec<String> {
+    enabled
+        .iter()
+        .flatten()
+        .filter(|name| {
+            !available
+                .iter()
+                .any(|available| available.eq_ignore_ascii_case(name))
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    use crate::config::profiles::InstallConfig;
+
+    fn profile(dir: &Path, use_windower: bool) -> Profile {
+        Profile {
+            server: Some("example".to_string()),
+            use_windower,
+            install: InstallConfig {
+                windower_directory: Some(dir.join("Windower")),
+                ashita_directory: Some(dir.join("Ashita")),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn moves_server_files_or_nothing() {
+        let dir = std::env::temp_dir().join("xi-launcher-convert-test");
+        let _ = fs::remove_dir_all(&dir);
+
+        let windower = profile(&dir, true);
+        let ashita = profile(&dir, false);
+        let bootloader = windower.get_bootloader_path().unwrap();
+        let dats = windower.get_pivot_dat_path().unwrap();
+        fs::create_dir_all(&bootloader).unwrap();
+        fs::create_dir_all(&dats).unwrap();
+
+        // The DATs can't be moved while a file is in the way of their new location
+        let blocker = ashita
+            .get_pivot_dat_path()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        fs::create_dir_all(blocker.parent().unwrap()).unwrap();
+        fs::write(&blocker, b"").unwrap();
+
+        let mut report = ConversionReport::default();
+        assert!(move_server_files(&windower, &ashita, &mut report).is_err());
+        assert!(bootloader.exists());
+        assert!(dats.exists());
+        assert!(!ashita.get_bootloader_path().unwrap().exists());
+        assert!(!report.moved_bootloader);
+
+        fs::remove_file(&blocker).unwrap();
+        let mut report = ConversionReport::default();
+        move_server_files(&windower, &ashita, &mut report).unwrap();
+        assert!(report.moved_bootloader && report.moved_dats);
+        assert!(ashita.get_pivot_dat_path().unwrap().exists());
+
+        // Saving the profile failed, so everything goes back
+        undo_moves(&windower, &ashita, &mut report);
+        assert!(bootloader.exists());
+        assert!(dats.exists());
+        assert!(!ashita.get_bootloader_path().unwrap().exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn restores_windower_settings() {
+        let dir = std::env::temp_dir().join("xi-launcher-convert-settings-test");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut profile = profile(&dir, true);
+        profile.windower_profile = Some("example".to_string());
+        let settings_path = dir.join("Windower").join("settings.xml");
+        fs::create_dir_all(settings_path.parent().unwrap()).unwrap();
+        let original = "<settings></settings>";
+        fs::write(&settings_path, original).unwrap();
+
+        let backup = write_windower_settings(&profile).unwrap().unwrap();
+        let written = fs::read_to_string(&settings_path).unwrap();
+        assert!(written.contains("name=\"example\""));
+
+        // Saving the profile failed, so settings.xml goes back
+        backup.restore();
+        assert_eq!(fs::read_to_string(&settings_path).unwrap(), original);
+
+        profile.use_windower = false;
+        assert!(write_windower_settings(&profile).unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use reqwest::{header::CONTENT_LENGTH, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use specta::Type;
+
+use crate::{
+    check_game::{get_signed_json, DownloadInfo, ServerErrorDetails, VersionsInfo},
+    config::profiles::Profile,
+    http,
+    manifest_cache::ManifestCache,
+    tasks::install::installer_parts,
+};
+
+const DOWNLOAD_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ServerDiagnosis {
+    pub info_url: Option<String>,
+    /// Problems with the profile's server configuration itself.
+    pub problems: Vec<String>,
+    pub endpoints: Vec<EndpointDiagnosis>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct EndpointDiagnosis {
+    pub endpoint: String,
+    pub url: Option<String>,
+    /// Whether the server provides the endpoint at all. Both endpoints are optional.
+    pub found: bool,
+    pub error: Option<ServerErrorDetails>,
+    pub problems: Vec<String>,
+}
+
+/// Requests every server info endpoint without using the manifest cache, and reports anything wrong with them.
+pub async fn diagnose_server(profile: &Profile) -> ServerDiagnosis {
+    let mut problems = vec![];
+
+    if profile.is_retail || profile.server.is_none() {
+        problems.push("The profile does not connect to a private server.".to_string());
+        return ServerDiagnosis {
+            info_url: None,
+            problems,
+            endpoints: vec![],
+        };
+    }
+
+    let info_url = match profile.get_server_info_url() {
+        Ok(info_url) => info_url,
+        Err(err) => {
+            problems.push(format!("Invalid server info address: {err:#}"));
+            return ServerDiagnosis {
+                info_url: None,
+                problems,
+                endpoints: vec![],
+            };
+        }
+    };
+
+    if info_url.scheme() == "http" {
+        problems.push(
+            "The server info address uses plain HTTP, so responses could be tampered with."
+                .to_string(),
+        );
+    }
+
+    let (versions_info, mut versions) =
+        diagnose_endpoint::<VersionsInfo>(profile, "versions").await;
+    if let Some(versions_info) = &versions_info {
+        versions
+            .problems
+            .extend(check_versions_info(profile, versions_info).await);
+    }
+
+    let (download_info, mut install) = diagnose_endpoint::<DownloadInfo>(profile, "install").await;
+    if let Some(download_info) = &download_info {
+        install
+            .problems
+            .extend(check_download_info(profile, download_info).await);
+    }
+
+    ServerDiagnosis {
+        info_url: Some(info_url.to_string()),
+        problems,
+        endpoints: vec![versions, install],
+    }
+}
+
+async fn diagnose_endpoint<T: DeserializeOwned>(
+    profile: &Profile,
+    endpoint: &str,
+) -> (Option<T>, EndpointDiagnosis) {
+    let mut diagnosis = EndpointDiagnosis {
+        endpoint: endpoint.to_string(),
+        url: profile.get_server_info_endpoint(endpoint).ok(),
+        found: false,
+        error: None,
+        problems: vec![],
+    };
+
+    let mut cache = ManifestCache::default();
+    match get_signed_json(profile, endpoint, &mut cache, Duration::ZERO).await {
+        Ok(Some(parsed)) => {
+            diagnosis.found = true;
+            (Some(parsed), diagnosis)
+        }
+        Ok(None) => (None, diagnosis),
+        Err(err) => {
+            diagnosis.found = true;
+            diagnosis.error = Some(err.details());
+            (None, diagnosis)
+        }
+    }
+}
+
+async fn check_versions_info(profile: &Profile, versions_info: &VersionsInfo) -> Vec<String> {
+    let mut problems = vec![];
+    let mut names = BTreeSet::new();
+
+    for component in versions_info.all_components() {
+        let name = &component.name;
+
+        if !names.insert(name.clone()) {
+            problems.push(format!("Component '{name}' is listed more than once."));
+        }
+
+        if component.version.trim().is_empty() {
+            problems.push(format!("Component '{name}' has an empty version."));
+        }
+
+        if let Some(sha256) = &component.sha256 {
+            if !is_sha256(sha256) {
+                problems.push(format!(
+                    "Component '{name}' has an invalid sha256: {sha256}"
+                ));
+            }
+        }
+
+        if let Err(err) = component.get_target_dir(profile) {
+            problems.push(format!("{err:#}"));
+        }
+        if let Err(err) = component.get_file_name() {
+            problems.push(format!("{err:#}"));
+        }
+
+        if let Some(problem) = probe_download(profile, &component.url, component.size).await {
+            problems.push(format!("Component '{name}': {problem}"));
+        }
+    }
+
+    for component in &versions_info.components {
+        if component.sha256.is_none() {
+            problems.push(format!(
+                "Component '{}' has no sha256, so the download can't be verified.",
+                component.name
+            ));
+        }
+    }
+
+    if let Some(client) = &versions_info.client {
+        if client.min_version.is_some() && client.exact_version.is_some() {
+            problems.push(
+                "The client requirement sets both a minimum and an exact version.".to_string(),
+            );
+        }
+    }
+
+    problems
+}
+
+async fn check_download_info(profile: &Profile, download_info: &DownloadInfo) -> Vec<String> {
+    let mut problems = vec![];
+
+    if download_info.is_empty() {
+        problems.push("The install manifest does not list any files.".to_string());
+    } else if let Err(err) = installer_parts(download_info) {
+        problems.push(err.to_string());
+    }
+
+    for file in download_info {
+        if let Some(sha256) = &file.sha256 {
+            if !is_sha256(sha256) {
+                problems.push(format!("{} has an invalid sha256: {sha256}", file.url));
+            }
+        } else {
+            problems.push(format!(
+                "{} has no sha256, so the download can't be verified.",
+                file.url
+            ));
+        }
+
+        for url in std::iter::once(&file.url).chain(&file.mirrors) {
+            if let Some(problem) = probe_download(profile, url, file.size).await {
+                problems.push(problem);
+            }
+        }
+    }
+
+    problems
+}
+
+/// Checks that a download URL is reachable, and has the expected size, without downloading it.
+async fn probe_download(
+    profile: &Profile,
+    url: &str,
+    expected_size: Option<u64>,
+) -> Option<String> {
+    if let Err(err) = Url::parse(url) {
+        return Some(format!("Invalid URL {url}: {err}"));
+    }
+
+    let client = match http::server_client(profile) {
+        Ok(client) => client,
+        Err(err) => return Some(format!("{err:#}")),
+    };
+
+    let response = match client
+        .head(url)
+        .timeout(DOWNLOAD_PROBE_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(err) => return Some(format!("Could not reach {url}: {err}")),
+    };
+
+    if !response.status().is_success() {
+        return Some(format!("{url} responded with {}", response.status()));
+    }
+
+    // Some servers don't include a length in HEAD responses
+    let length = response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    match (expected_size, length) {
+        (Some(expected), Some(length)) if expected != length => Some(format!(
+            "{url} is {length} bytes, but the manifest expects {expected} bytes."
+        )),
+        _ => None,
+    }
+}
+
+fn is_sha256(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
@@ -0,0 +1,387 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specta::Type;
+
+use crate::util::load_json_or_default;
+
+pub const DOWNLOAD_CACHE_DIRNAME: &str = "download_cache";
+
+const INDEX_FILENAME: &str = "index.json";
+
+/// Extension of objects that are still being copied into the cache.
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Downloads shared by every profile, stored by their SHA-256 so servers serving the same file share it.
+/// Files are matched by the SHA-256 a manifest declares. Files without one are matched by their URL,
+/// but only if the manifest declares a size that still matches, since servers can publish a new file
+/// at the same URL.
+pub struct DownloadCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<CacheIndex>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    /// Cached files keyed by their SHA-256.
+    #[serde(default)]
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+
+    /// URLs the file was downloaded from, to find files that manifests don't declare a SHA-256 for.
+    #[serde(default)]
+    urls: BTreeSet<String>,
+
+    /// Unix timestamp of when the file was last stored or reused.
+    last_used: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Type)]
+pub struct PruneReport {
+    pub removed_files: u32,
+    pub freed_bytes: u64,
+    pub remaining_bytes: u64,
+}
+
+impl DownloadCache {
+    /// Opens the cache in `local_data_dir`. A `max_bytes` of 0 disables storing new files.
+    pub fn new(local_data_dir: &Path, max_bytes: u64) -> Self {
+        let dir = local_data_dir.join(DOWNLOAD_CACHE_DIRNAME);
+        let index = load_json_or_default(&dir.join(INDEX_FILENAME));
+
+        Self {
+            dir,
+            max_bytes,
+            index: Mutex::new(index),
+        }
+    }
+
+    fn object_path(&self, sha256: &str) -> PathBuf {
+        self.dir.join("objects").join(sha256)
+    }
+
+    /// Places a cached copy of the file with the given SHA-256 at `target`, or without one, of the file
+    /// last downloaded from `url` if it has the given size. Returns the SHA-256 of the restored file.
+    pub fn restore(
+        &self,
+        url: &str,
+        sha256: Option<&str>,
+        size: Option<u64>,
+        target: &Path,
+    ) -> anyhow::Result<Option<String>> {
+        let index = self.index.lock().unwrap();
+
+        let key = match (sha256, size) {
+            (Some(sha256), _) => {
+                Some(sha256.to_ascii_lowercase()).filter(|key| index.entries.contains_key(key))
+            }
+            (None, Some(size)) => index
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.size == size && entry.urls.contains(url))
+                .max_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone()),
+            (None, None) => None,
+        };
+        let Some(key) = key else {
+            return Ok(None);
+        };
+        drop(index);
+
+        // Files can be several gigabytes, so they are copied and hashed without holding the lock
+        let object_path = self.object_path(&key);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _ = fs::remove_file(target);
+        copy_file(&object_path, target)?;
+
+        // Make sure the object wasn't damaged since it was stored
+        let matches = matches!(hash_file(target), Ok((_, sha256)) if sha256 == key);
+        let mut index = self.index.lock().unwrap();
+        if !matches {
+            tracing::warn!("Removing damaged download cache entry {key}");
+            let _ = fs::remove_file(target);
+            index.entries.remove(&key);
+            let _ = fs::remove_file(&object_path);
+            self.save(&index)?;
+            return Ok(None);
+        }
+
+        if let Some(entry) = index.entries.get_mut(&key) {
+            entry.urls.insert(url.to_string());
+            entry.last_used = now();
+        }
+        self.save(&index)?;
+
+        tracing::info!("Reusing cached download of {url}");
+        Ok(Some(key))
+    }
+
+    /// Adds a downloaded file, which was verified to have the given SHA-256, to the cache.
+    /// Without a SHA-256, the file is hashed first.
+    pub fn store(&self, path: &Path, url: &str, sha256: Option<&str>) -> anyhow::Result<()> {
+        if self.max_bytes == 0 {
+            return Ok(());
+        }
+
+        let size = fs::metadata(path)?.len();
+        if size > self.max_bytes {
+            return Ok(());
+        }
+        let sha256 = match sha256 {
+            Some(sha256) => sha256.to_ascii_lowercase(),
+            None => hash_file(path)?.1,
+        };
+
+        // Copied without holding the lock, since pruning skips the temporary file
+        let object_path = self.object_path(&sha256);
+        let temp_path = object_path.with_extension(TEMP_EXTENSION);
+        let copied = !object_path.exists();
+        if copied {
+            fs::create_dir_all(self.dir.join("objects"))?;
+            let _ = fs::remove_file(&temp_path);
+            copy_file(path, &temp_path)?;
+        }
+
+        // The object only appears together with its entry, so pruning can't mistake it for an unknown file
+        let mut index = self.index.lock().unwrap();
+        if copied {
+            fs::rename(&temp_path, &object_path)?;
+        }
+
+        let entry = index.entries.entry(sha256.clone()).or_insert(CacheEntry {
+            size,
+            urls: BTreeSet::new(),
+            last_used: 0,
+        });
+        entry.urls.insert(url.to_string());
+        entry.last_used = now();
+
+        self.evict(&mut index, self.max_bytes, Some(&sha256));
+        self.save(&index)
+    }
+
+    /// Removes the least recently used files until the cache is at most `max_bytes`,
+    /// along with any files the index doesn't know about.
+    pub fn prune(&self, max_bytes: u64) -> anyhow::Result<PruneReport> {
+        let mut index = self.index.lock().unwrap();
+
+        let mut report = self.evict(&mut index, max_bytes, None);
+
+        if let Ok(read_dir) = fs::read_dir(self.dir.join("objects")) {
+            for entry in read_dir.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let is_temp = Path::new(&name)
+                    .extension()
+                    .is_some_and(|extension| extension == TEMP_EXTENSION);
+                if !is_temp && !index.entries.contains_key(&name) {
+                    report.freed_bytes += entry.metadata().map(|m| m.len()).unwrap_or_default();
+                    report.removed_files += 1;
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+
+        self.save(&index)?;
+        Ok(report)
+    }
+
+    fn evict(&self, index: &mut CacheIndex, max_bytes: u64, keep: Option<&str>) -> PruneReport {
+        let mut report = PruneReport {
+            remaining_bytes: index.entries.values().map(|entry| entry.size).sum(),
+            ..Default::default()
+        };
+
+        let mut by_age: Vec<(String, CacheEntry)> = index
+            .entries
+            .iter()
+            .filter(|(sha256, _)| Some(sha256.as_str()) != keep)
+            .map(|(sha256, entry)| (sha256.clone(), entry.clone()))
+            .collect();
+        by_age.sort_by_key(|(_, entry)| entry.last_used);
+
+        for (sha256, entry) in by_age {
+            if report.remaining_bytes <= max_bytes {
+                break;
+            }
+
+            index.entries.remove(&sha256);
+            let _ = fs::remove_file(self.object_path(&sha256));
+            report.remaining_bytes -= entry.size;
+            report.freed_bytes += entry.size;
+            report.removed_files += 1;
+        }
+
+        report
+    }
+
+    fn save(&self, index: &CacheIndex) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let file = File::create(self.dir.join(INDEX_FILENAME))?;
+        serde_json::to_writer(file, index)?;
+        Ok(())
+    }
+}
+
+/// Copies the file. Hardlinks would be faster, but a profile changing its copy in place would then
+/// change the cached file for every other profile as well.
+fn copy_file(source: &Path, target: &Path) -> anyhow::Result<()> {
+    fs::copy(source, target).map_err(|err| {
+        anyhow!(
+            "Could not copy {} to {}: {err}",
+            source.display(),
+            target.display()
+        )
+    })?;
+    Ok(())
+}
+
+fn hash_file(path: &Path) -> anyhow::Result<(u64, String)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher)?;
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_restores_and_prunes() {
+        let dir = std::env::temp_dir().join("xi-launcher-download-cache-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let cache = DownloadCache::new(&dir, 8);
+        let download = dir.join("a.exe");
+        fs::write(&download, b"hello").unwrap();
+        let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        cache
+            .store(&download, "https://one.example.com/a.exe", Some(sha256))
+            .unwrap();
+
+        // Other URLs serving the same file share it
+        let target = dir.join("other/a.exe");
+        assert_eq!(
+            cache
+                .restore("https://two.example.com/a.exe", Some(sha256), None, &target)
+                .unwrap()
+                .as_deref(),
+            Some(sha256)
+        );
+        assert_eq!(fs::read(&target).unwrap(), b"hello");
+
+        // Changing a restored file doesn't change the cached one
+        fs::write(&target, b"jello").unwrap();
+        let other_target = dir.join("another/a.exe");
+        assert!(cache
+            .restore(
+                "https://two.example.com/a.exe",
+                Some(sha256),
+                None,
+                &other_target
+            )
+            .unwrap()
+            .is_some());
+        assert_eq!(fs::read(&other_target).unwrap(), b"hello");
+
+        // A new file at the same URL is never matched
+        assert!(cache
+            .restore(
+                "https://one.example.com/a.exe",
+                Some(&"0".repeat(64)),
+                Some(5),
+                &dir.join("b.exe")
+            )
+            .unwrap()
+            .is_none());
+
+        // Without a SHA-256, the URL is matched as long as the size is the same
+        assert!(cache
+            .restore(
+                "https://one.example.com/a.exe",
+                None,
+                Some(6),
+                &dir.join("c.exe")
+            )
+            .unwrap()
+            .is_none());
+        assert!(cache
+            .restore(
+                "https://three.example.com/a.exe",
+                None,
+                Some(5),
+                &dir.join("c.exe")
+            )
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            cache
+                .restore(
+                    "https://one.example.com/a.exe",
+                    None,
+                    Some(5),
+                    &dir.join("c.exe")
+                )
+                .unwrap()
+                .as_deref(),
+            Some(sha256)
+        );
+
+        // Going over the limit evicts the older file
+        let second = dir.join("b.7z");
+        fs::write(&second, b"world").unwrap();
+        let second_sha256 = "486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7";
+        cache
+            .store(&second, "https://one.example.com/b.7z", None)
+            .unwrap();
+        assert!(cache
+            .restore(
+                "https://one.example.com/a.exe",
+                Some(sha256),
+                None,
+                &dir.join("d.exe")
+            )
+            .unwrap()
+            .is_none());
+        assert!(cache
+            .restore(
+                "https://one.example.com/b.7z",
+                Some(second_sha256),
+                None,
+                &dir.join("e.7z")
+            )
+            .unwrap()
+            .is_some());
+
+        let report = cache.prune(0).unwrap();
+        assert_eq!(report.removed_files, 1);
+        assert_eq!(report.freed_bytes, 5);
+        assert_eq!(report.remaining_bytes, 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,727 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use sevenz_rust::{Password, SevenZReader};
+use tokio_util::sync::CancellationToken;
+
+const SEVEN_Z_SIGNATURE: &[u8] = &[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C];
+
+/// How far into a self-extracting executable the archive is searched for.
+const MAX_SFX_STUB_LENGTH: u64 = 64 * 1024 * 1024;
+
+/// Directory in the output directory that archives are extracted to, before being moved into place.
+const STAGING_DIRNAME: &str = ".extracting";
+
+/// Directory in the output directory that replaced files are kept in, until everything was moved into place.
+const REPLACED_DIRNAME: &str = ".replaced";
+
+const COPY_BUFFER_LENGTH: usize = 256 * 1024;
+
+enum ArchiveFormat {
+    /// A 7z archive, starting at the offset. Self-extracting executables have a stub before the archive.
+    SevenZip { offset: u64 },
+    /// Zip archives are read from the end, so a stub before them doesn't matter.
+    Zip,
+}
+
+/// Extracts a 7z or zip archive, or the archive in a self-extracting executable, into `output_dir`.
+/// Archives split into several parts are read as if the parts were one file, in the given order.
+/// Nothing is left behind in `output_dir` if extracting fails or is cancelled.
+pub fn extract_archive(
+    parts: &[PathBuf],
+    output_dir: &Path,
+    token: &CancellationToken,
+    mut progress: impl FnMut(u64, u64),
+) -> anyhow::Result<()> {
+    let format = detect_format(parts)?;
+
+    let staging_dir = output_dir.join(STAGING_DIRNAME);
+    let _ = fs::remove_dir_all(&staging_dir);
+    fs::create_dir_all(&staging_dir)?;
+
+    let result = match format {
+        ArchiveFormat::SevenZip { offset } => {
+            extract_7z(parts, offset, &staging_dir, token, &mut progress)
+        }
+        ArchiveFormat::Zip => extract_zip(parts, &staging_dir, token, &mut progress),
+    }
+    .and_then(|()| move_into(&staging_dir, output_dir));
+
+    let _ = fs::remove_dir_all(&staging_dir);
+    result
+}
+
+/// Copies the contents of `source` into `output_dir`, like extracting an archive of it,
+/// with the same guarantees as [extract_archive] when it fails.
+pub fn copy_directory(
+    source: &Path,
+    output_dir: &Path,
+    token: &CancellationToken,
+    mut progress: impl FnMut(u64, u64),
+) -> anyhow::Result<()> {
+    let mut files = vec![];
+    list_files(source, Path::new(""), &mut files)?;
+    let total_length = files.iter().map(|(_, length)| length).sum();
+
+    let staging_dir = output_dir.join(STAGING_DIRNAME);
+    let _ = fs::remove_dir_all(&staging_dir);
+    fs::create_dir_all(&staging_dir)?;
+
+    let mut finished_length = 0;
+    progress(finished_length, total_length);
+    let result = files
+        .iter()
+        .try_for_each(|(relative_path, _)| {
+            let source_path = source.join(relative_path);
+            let mut reader = File::open(&source_path)
+                .with_context(|| format!("Could not open {}", source_path.display()))?;
+            write_file(
+                &mut reader,
+                &staging_dir.join(relative_path),
+                token,
+                &mut |length| {
+                    finished_length += length;
+                    progress(finished_length, total_length);
+                },
+            )
+        })
+        .and_then(|()| move_into(&staging_dir, output_dir));
+
+    let _ = fs::remove_dir_all(&staging_dir);
+    result
+}
+
+/// Lists the files in `dir` with their lengths, relative to the directory being copied.
+fn list_files(
+    dir: &Path,
+    relative_dir: &Path,
+    files: &mut Vec<(PathBuf, u64)>,
+) -> anyhow::Result<()> {
+    let read_dir =
+        fs::read_dir(dir).with_context(|| format!("Could not read {}", dir.display()))?;
+    for entry in read_dir {
+        let entry = entry?;
+        let relative_path = relative_dir.join(entry.file_name());
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            list_files(&entry.path(), &relative_path, files)?;
+        } else if file_type.is_file() {
+            files.push((relative_path, entry.metadata()?.len()));
+        }
+    }
+
+    Ok(())
+}
+
+fn detect_format(parts: &[PathBuf]) -> anyhow::Result<ArchiveFormat> {
+    let mut reader = MultiPartReader::open(parts)?;
+    let length = reader.length;
+
+    for offset in find_signatures(
+        &mut reader,
+        SEVEN_Z_SIGNATURE,
+        length.min(MAX_SFX_STUB_LENGTH),
+    )? {
+        // The signature can also appear by chance in the stub, so check that an archive actually starts there
+        let reader = OffsetReader::new(MultiPartReader::open(parts)?, offset)?;
+        if SevenZReader::new(reader, length - offset, Password::empty()).is_ok() {
+            return Ok(ArchiveFormat::SevenZip { offset });
+        }
+    }
+
+    if zip::ZipArchive::new(MultiPartReader::open(parts)?).is_ok() {
+        return Ok(ArchiveFormat::Zip);
+    }
+
+    let name = parts
+        .first()
+        .map(|part| part.display().to_string())
+        .unwrap_or_default();
+    Err(anyhow!(
+        "{name} is not a 7z or zip archive, or a self-extracting archive."
+    ))
+}
+
+/// Returns the offsets of the signature in the first `limit` bytes of the reader.
+fn find_signatures(reader: &mut impl Read, signature: &[u8], limit: u64) -> io::Result<Vec<u64>> {
+    let mut offsets = vec![];
+    let mut reader = reader.take(limit);
+    let mut buffer = vec![0; COPY_BUFFER_LENGTH];
+    // The end of the previous chunk, so signatures crossing chunks are found
+    let mut window: Vec<u8> = vec![];
+    let mut window_offset = 0u64;
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        window.extend_from_slice(&buffer[..read]);
+
+        for (i, candidate) in window.windows(signature.len()).enumerate() {
+            if candidate == signature {
+                offsets.push(window_offset + i as u64);
+            }
+        }
+
+        let keep = (signature.len() - 1).min(window.len());
+        window_offset += (window.len() - keep) as u64;
+        window.drain(..window.len() - keep);
+    }
+
+    Ok(offsets)
+}
+
+fn extract_7z(
+    parts: &[PathBuf],
+    offset: u64,
+    output_dir: &Path,
+    token: &CancellationToken,
+    progress: &mut impl FnMut(u64, u64),
+) -> anyhow::Result<()> {
+    let reader = MultiPartReader::open(parts)?;
+    let length = reader.length;
+    let reader = OffsetReader::new(reader, offset)?;
+    let mut archive = SevenZReader::new(reader, length - offset, Password::empty())?;
+
+    let total_length = archive
+        .archive()
+        .files
+        .iter()
+        .map(|entry| entry.size())
+        .sum();
+    let mut finished_length = 0u64;
+    progress(finished_length, total_length);
+
+    let mut error = None;
+    let result = archive.for_each_entries(|entry, reader| {
+        let extracted = extract_entry(
+            entry.name(),
+            entry.is_directory(),
+            reader,
+            output_dir,
+            token,
+            &mut |length| {
+                finished_length += length;
+                progress(finished_length, total_length);
+            },
+        );
+
+        match extracted {
+            Ok(()) => Ok(true),
+            Err(err) => {
+                error = Some(err);
+                Err(sevenz_rust::Error::other("Extraction stopped."))
+            }
+        }
+    });
+
+    match (error, result) {
+        (Some(err), _) => Err(err),
+        (None, Err(err)) => Err(anyhow!("Invalid 7z archive: {err:?}")),
+        (None, Ok(())) => Ok(()),
+    }
+}
+
+fn extract_zip(
+    parts: &[PathBuf],
+    output_dir: &Path,
+    token: &CancellationToken,
+    progress: &mut impl FnMut(u64, u64),
+) -> anyhow::Result<()> {
+    let mut archive = zip::ZipArchive::new(MultiPartReader::open(parts)?)?;
+
+    let mut total_length = 0;
+    for i in 0..archive.len() {
+        total_length += archive.by_index_raw(i)?.size();
+    }
+    let mut finished_length = 0u64;
+    progress(finished_length, total_length);
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        let is_directory = entry.is_dir();
+
+        extract_entry(
+            &name,
+            is_directory,
+            &mut entry,
+            output_dir,
+            token,
+            &mut |length| {
+                finished_length += length;
+                progress(finished_length, total_length);
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+fn extract_entry(
+    name: &str,
+    is_directory: bool,
+    reader: &mut dyn Read,
+    output_dir: &Path,
+    token: &CancellationToken,
+    on_chunk: &mut dyn FnMut(u64),
+) -> anyhow::Result<()> {
+    if token.is_cancelled() {
+        return Err(anyhow!("Extraction cancelled."));
+    }
+
+    // Archives may have an entry for their root directory
+    if is_directory
+        && name
+            .split(['/', '\\'])
+            .all(|part| part.is_empty() || part == ".")
+    {
+        return Ok(());
+    }
+
+    let path = output_dir.join(
+        safe_relative_path(name)
+            .ok_or_else(|| anyhow!("Archive contains an unsafe path: {name}"))?,
+    );
+
+    if is_directory {
+        fs::create_dir_all(&path)?;
+        return Ok(());
+    }
+
+    write_file(reader, &path, token, on_chunk)
+}
+
+fn write_file(
+    reader: &mut dyn Read,
+    path: &Path,
+    token: &CancellationToken,
+    on_chunk: &mut dyn FnMut(u64),
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut output = BufWriter::new(
+        File::create(path).with_context(|| format!("Could not create {}", path.display()))?,
+    );
+    let mut buffer = vec![0; COPY_BUFFER_LENGTH];
+    loop {
+        if token.is_cancelled() {
+            return Err(anyhow!("Extraction cancelled."));
+        }
+
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        output.write_all(&buffer[..read])?;
+        on_chunk(read as u64);
+    }
+    output.flush()?;
+
+    Ok(())
+}
+
+/// Returns the entry name as a relative path, or `None` if it would end up outside of the output directory.
+fn safe_relative_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+
+    // Archives made on Windows may use either separator
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            part if part.contains(':') => return None,
+            part => path.push(part),
+        }
+    }
+
+    let is_relative = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    (is_relative && path.components().next().is_some()).then_some(path)
+}
+
+/// An entry moved into the output directory, and where the entry it replaced is kept.
+struct MovedEntry {
+    target: PathBuf,
+    replaced: Option<PathBuf>,
+}
+
+/// Moves everything in `from` into `to`, merging directories that already exist.
+/// If moving fails partway, the entries moved so far are removed and the ones they replaced are put back.
+fn move_into(from: &Path, to: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(to)?;
+
+    let replaced_dir = to.join(REPLACED_DIRNAME);
+    let _ = fs::remove_dir_all(&replaced_dir);
+
+    let mut moved = vec![];
+    let result = move_entries(from, to, &replaced_dir, &mut moved);
+    if result.is_err() {
+        undo_moves(moved);
+    }
+
+    let _ = fs::remove_dir_all(&replaced_dir);
+    result
+}
+
+fn move_entries(
+    from: &Path,
+    to: &Path,
+    replaced_dir: &Path,
+    moved: &mut Vec<MovedEntry>,
+) -> anyhow::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() && target.is_dir() {
+            move_entries(&entry.path(), &target, replaced_dir, moved)?;
+            continue;
+        }
+
+        // Replaced entries are set aside instead of removed, so they can be put back
+        let replaced = if target.exists() {
+            fs::create_dir_all(replaced_dir)?;
+            let replaced = replaced_dir.join(moved.len().to_string());
+            fs::rename(&target, &replaced)
+                .with_context(|| format!("Could not replace {}", target.display()))?;
+            Some(replaced)
+        } else {
+            None
+        };
+
+        let result = fs::rename(entry.path(), &target)
+            .with_context(|| format!("Could not move extracted {}", target.display()));
+        if let Err(err) = result {
+            if let Some(replaced) = replaced {
+                let _ = fs::rename(replaced, &target);
+            }
+            return Err(err);
+        }
+
+        moved.push(MovedEntry { target, replaced });
+    }
+
+    Ok(())
+}
+
+/// Removes the moved entries, newest first, and puts back the entries they replaced.
+fn undo_moves(moved: Vec<MovedEntry>) {
+    for MovedEntry { target, replaced } in moved.into_iter().rev() {
+        let removed = if target.is_dir() {
+            fs::remove_dir_all(&target)
+        } else {
+            fs::remove_file(&target)
+        };
+        if let Err(err) = removed {
+            tracing::error!("Could not remove {}: {err}", target.display());
+            continue;
+        }
+
+        if let Some(replaced) = replaced {
+            if let Err(err) = fs::rename(&replaced, &target) {
+                tracing::error!("Could not put back {}: {err}", target.display());
+            }
+        }
+    }
+}
+
+/// Reads the parts of a split archive as one stream.
+struct MultiPartReader {
+    parts: Vec<Part>,
+    length: u64,
+    position: u64,
+}
+
+struct Part {
+    reader: BufReader<File>,
+    length: u64,
+    /// Position of `reader`, so it is only seeked when needed, which would discard its buffer.
+    position: u64,
+}
+
+impl MultiPartReader {
+    fn open(parts: &[PathBuf]) -> anyhow::Result<Self> {
+        if parts.is_empty() {
+            return Err(anyhow!("No archive to extract."));
+        }
+
+        let mut opened = vec![];
+        for (i, path) in parts.iter().enumerate() {
+            let file = File::open(path).with_context(|| {
+                format!(
+                    "Part {} of the archive is missing: {}",
+                    i + 1,
+                    path.display()
+                )
+            })?;
+            opened.push(Part {
+                length: file.metadata()?.len(),
+                reader: BufReader::new(file),
+                position: 0,
+            });
+        }
+
+        Ok(Self {
+            length: opened.iter().map(|part| part.length).sum(),
+            parts: opened,
+            position: 0,
+        })
+    }
+}
+
+impl Read for MultiPartReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut part_start = 0;
+        for part in &mut self.parts {
+            let part_end = part_start + part.length;
+            if self.position < part_end {
+                let position = self.position - part_start;
+                if part.position != position {
+                    part.reader.seek(SeekFrom::Start(position))?;
+                    part.position = position;
+                }
+
+                let available = (part_end - self.position).min(buf.len() as u64) as usize;
+                let read = part.reader.read(&mut buf[..available])?;
+                part.position += read as u64;
+                self.position += read as u64;
+                return Ok(read);
+            }
+            part_start = part_end;
+        }
+
+        Ok(0)
+    }
+}
+
+impl Seek for MultiPartReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(offset) => self.length.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Seek before the start of the archive",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// Presents the part of a reader after `offset` as if it were the whole stream.
+struct OffsetReader<R> {
+    inner: R,
+    offset: u64,
+}
+
+impl<R: Seek> OffsetReader<R> {
+    fn new(mut inner: R, offset: u64) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(offset))?;
+        Ok(Self { inner, offset })
+    }
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for OffsetReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => {
+                self.inner.seek(SeekFrom::Start(self.offset + position))?
+            }
+            pos => self.inner.seek(pos)?,
+        };
+
+        position.checked_sub(self.offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Seek before the start of the archive",
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unsafe_entry_names() {
+        assert_eq!(
+            safe_relative_path("FINAL FANTASY XI/ROM/0/0.DAT"),
+            Some(
+                PathBuf::from("FINAL FANTASY XI")
+                    .join("ROM")
+                    .join("0")
+                    .join("0.DAT")
+            )
+        );
+        assert_eq!(
+            safe_relative_path("./PlayOnline\\SquareEnix"),
+            Some(PathBuf::from("PlayOnline").join("SquareEnix"))
+        );
+        assert_eq!(
+            safe_relative_path("/etc/passwd"),
+            Some(PathBuf::from("etc/passwd"))
+        );
+        assert_eq!(safe_relative_path("../x.exe"), None);
+        assert_eq!(safe_relative_path("a/../../x.exe"), None);
+        assert_eq!(safe_relative_path("..\\x.exe"), None);
+        assert_eq!(safe_relative_path("C:\\Windows\\x.exe"), None);
+        assert_eq!(safe_relative_path(""), None);
+    }
+
+    #[test]
+    fn undoes_moves_into_place() {
+        let dir = std::env::temp_dir().join("xi-launcher-move-into-test");
+        let _ = fs::remove_dir_all(&dir);
+        let from = dir.join("from");
+        let to = dir.join("to");
+        fs::create_dir_all(from.join("ROM")).unwrap();
+        fs::create_dir_all(to.join("ROM")).unwrap();
+        fs::write(from.join("ROM/0.DAT"), b"new").unwrap();
+        fs::write(from.join("xiloader.exe"), b"new").unwrap();
+        fs::write(to.join("ROM/0.DAT"), b"old").unwrap();
+        fs::write(to.join("ROM/1.DAT"), b"old").unwrap();
+
+        let replaced_dir = to.join(REPLACED_DIRNAME);
+        let mut moved = vec![];
+        move_entries(&from, &to, &replaced_dir, &mut moved).unwrap();
+        assert_eq!(fs::read(to.join("ROM/0.DAT")).unwrap(), b"new");
+        assert_eq!(fs::read(to.join("xiloader.exe")).unwrap(), b"new");
+
+        // A later entry failed to move, so everything goes back
+        undo_moves(moved);
+        assert_eq!(fs::read(to.join("ROM/0.DAT")).unwrap(), b"old");
+        assert_eq!(fs::read(to.join("ROM/1.DAT")).unwrap(), b"old");
+        assert!(!to.join("xiloader.exe").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn finds_signatures_across_chunks() {
+        let mut data = vec![0u8; COPY_BUFFER_LENGTH - 3];
+        data.extend_from_slice(SEVEN_Z_SIGNATURE);
+        data.extend_from_slice(&[1, 2, 3]);
+        data.extend_from_slice(SEVEN_Z_SIGNATURE);
+
+        let offsets = find_signatures(&mut data.as_slice(), SEVEN_Z_SIGNATURE, u64::MAX).unwrap();
+        assert_eq!(
+            offsets,
+            vec![
+                (COPY_BUFFER_LENGTH - 3) as u64,
+                (COPY_BUFFER_LENGTH + 6) as u64
+            ]
+        );
+    }
+
+    #[test]
+    fn extracts_self_extracting_7z() {
+        let dir = std::env::temp_dir().join("xi-launcher-extract-test");
+        let _ = fs::remove_dir_all(&dir);
+        let source_dir = dir.join("source/FINAL FANTASY XI");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("VTABLE.DAT"), b"vtable").unwrap();
+
+        let archive_path = dir.join("archive.7z");
+        sevenz_rust::compress_to_path(dir.join("source"), &archive_path).unwrap();
+
+        // A stub in front of the archive, like a self-extracting executable
+        let mut sfx = b"MZ stub 7z".to_vec();
+        sfx.extend_from_slice(&fs::read(&archive_path).unwrap());
+        let sfx_path = dir.join("setup.exe");
+        fs::write(&sfx_path, sfx).unwrap();
+
+        let output_dir = dir.join("install");
+        let mut last_progress = (0, 0);
+        extract_archive(
+            std::slice::from_ref(&sfx_path),
+            &output_dir,
+            &CancellationToken::new(),
+            |done, total| last_progress = (done, total),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read(output_dir.join("FINAL FANTASY XI/VTABLE.DAT")).unwrap(),
+            b"vtable"
+        );
+        assert_eq!(last_progress, (6, 6));
+        assert!(!output_dir.join(STAGING_DIRNAME).exists());
+
+        // Split volumes, with the stub as the first part
+        let sfx = fs::read(&sfx_path).unwrap();
+        let (first, rest) = sfx.split_at(20);
+        let (second, third) = rest.split_at(rest.len() / 2);
+        let parts: Vec<PathBuf> = [first, second, third]
+            .iter()
+            .enumerate()
+            .map(|(i, data)| {
+                let path = dir.join(format!("archive.7z.{:03}", i + 1));
+                fs::write(&path, data).unwrap();
+                path
+            })
+            .collect();
+
+        let split_dir = dir.join("split");
+        extract_archive(&parts, &split_dir, &CancellationToken::new(), |_, _| {}).unwrap();
+        assert_eq!(
+            fs::read(split_dir.join("FINAL FANTASY XI/VTABLE.DAT")).unwrap(),
+            b"vtable"
+        );
+
+        fs::remove_file(&parts[1]).unwrap();
+        let err = extract_archive(
+            &parts,
+            &dir.join("missing"),
+            &CancellationToken::new(),
+            |_, _| {},
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("Part 2 of the archive is missing"));
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let cancelled_dir = dir.join("cancelled");
+        assert!(extract_archive(&[sfx_path], &cancelled_dir, &token, |_, _| {}).is_err());
+        assert!(!cancelled_dir.join("FINAL FANTASY XI").exists());
+        assert!(!cancelled_dir.join(STAGING_DIRNAME).exists());
+
+        // Existing clients are copied the same way
+        let copy_dir = dir.join("copy");
+        let mut last_progress = (0, 0);
+        copy_directory(
+            &dir.join("source"),
+            &copy_dir,
+            &CancellationToken::new(),
+            |done, total| last_progress = (done, total),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read(copy_dir.join("FINAL FANTASY XI/VTABLE.DAT")).unwrap(),
+            b"vtable"
+        );
+        assert_eq!(last_progress, (6, 6));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,223 +1,1114 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use anyhow::anyhow;
 use futures_util::StreamExt;
-use reqwest::Response;
+use reqwest::{
+    header::{
+        HeaderName, CONTENT_DISPOSITION, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE,
+    },
+    Client, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specta::Type;
 use tokio::{
-    fs::{self, File},
-    io::AsyncWriteExt,
+    fs::{self, File, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
     sync::mpsc,
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::task_manager::TaskManager;
+use crate::{bandwidth, filename, task_manager::TaskManager};
 
 #[derive(Debug)]
 pub enum DownloadMessage {
     GatheringData,
-    Started { content_length: u64 },
-    Progress { progress_length: u64 },
-    FileDone { path: PathBuf },
+    Started {
+        content_length: u64,
+    },
+    /// Overall progress. `content_length` grows as the lengths of files become known.
+    Progress {
+        progress_length: u64,
+        content_length: u64,
+        bytes_per_second: u64,
+        eta_secs: Option<u64>,
+    },
+    FileProgress(FileProgress),
+    AttemptFailed(AttemptFailure),
+    FileDone {
+        url: String,
+        path: PathBuf,
+    },
     Done,
-    Error { message: String },
+    Error {
+        message: String,
+    },
 }
 
+/// Messages from the task downloading the file at `index`.
 #[derive(Debug)]
 pub enum FileDownloadMessage {
-    Chunk { chunk_length: u64 },
-    Error { path: PathBuf, message: String },
-    Done { path: PathBuf },
+    Started {
+        index: usize,
+        name: String,
+        offset: u64,
+        total_length: Option<u64>,
+    },
+    Chunk {
+        index: usize,
+        chunk_length: u64,
+    },
+    AttemptFailed(AttemptFailure),
+    Error {
+        index: usize,
+        path: PathBuf,
+        message: String,
+    },
+    Done {
+        index: usize,
+        path: PathBuf,
+    },
 }
 
-pub async fn download_files(
-    urls: impl IntoIterator<Item = String>,
-    output_dir: PathBuf,
-    tx: mpsc::Sender<DownloadMessage>,
-    token: CancellationToken,
-) -> anyhow::Result<()> {
-    fs::create_dir_all(&output_dir).await?;
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct FileProgress {
+    pub url: String,
+    pub name: String,
+    pub finished_length: u64,
+    pub total_length: Option<u64>,
+    pub bytes_per_second: u64,
+    pub eta_secs: Option<u64>,
+}
 
-    tx.send(DownloadMessage::GatheringData).await?;
+/// A failed request for a file, which is retried or continued at another mirror.
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct AttemptFailure {
+    /// The primary URL of the file.
+    pub url: String,
+    /// The URL that was requested, which is one of the file's mirrors.
+    pub source: String,
+    pub attempt: u32,
+    pub message: String,
+    /// When the same source is retried. `None` when moving on to the next mirror, or giving up.
+    pub retry_in_ms: Option<u64>,
+}
 
-    let mut full_content_length = 0u64;
-    let mut responses = vec![];
+/// A file to download, along with mirrors that serve the exact same file.
+#[derive(Debug, Clone, Default)]
+pub struct DownloadFile {
+    pub url: String,
+    pub mirrors: Vec<String>,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+}
 
-    for url in urls.into_iter() {
-        if let Some(filename) = get_filename_from_url(&url) {
-            let output_path = output_dir.join(filename);
+impl DownloadFile {
+    fn sources(&self) -> Vec<&String> {
+        std::iter::once(&self.url).chain(&self.mirrors).collect()
+    }
 
-            if output_path.exists() {
-                // File already exists
-                tx.send(DownloadMessage::FileDone { path: output_path })
-                    .await?;
-                continue;
+    fn has_expectations(&self) -> bool {
+        self.size.is_some() || self.sha256.is_some()
+    }
+
+    /// Checks a downloaded length and hash against the ones from the manifest.
+    fn verify(&self, length: u64, sha256: Option<&str>) -> anyhow::Result<()> {
+        if let Some(expected_size) = self.size {
+            if expected_size != length {
+                return Err(anyhow!(
+                    "{} has a size of {length} bytes, but expected {expected_size} bytes.",
+                    self.url
+                ));
             }
         }
 
-        let response = reqwest::get(&url).await?;
-        full_content_length += response.content_length().unwrap_or_default();
+        if let (Some(expected_sha256), Some(sha256)) = (&self.sha256, sha256) {
+            if !expected_sha256.eq_ignore_ascii_case(sha256) {
+                return Err(anyhow!(
+                    "{} has checksum {sha256}, but expected {expected_sha256}.",
+                    self.url
+                ));
+            }
+        }
 
-        let filename = get_filename_from_response_or_url(&response, &url);
-        let output_path = output_dir.join(filename);
+        Ok(())
+    }
+}
+
+/// How often a source is requested before moving on to the next mirror.
+const ATTEMPTS_PER_SOURCE: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// How often a download may be interrupted without making any progress, before giving up.
+const MAX_STALLED_INTERRUPTIONS: u32 = 3;
 
-        responses.push((output_path, response));
+/// How often progress is reported while downloading.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Tracks the transfer rate of a download, to estimate when it will finish.
+#[derive(Debug, Clone)]
+pub struct RateTracker {
+    window_start: Instant,
+    window_length: u64,
+    bytes_per_second: u64,
+}
+
+impl Default for RateTracker {
+    fn default() -> Self {
+        Self {
+            window_start: Instant::now(),
+            window_length: 0,
+            bytes_per_second: 0,
+        }
     }
+}
 
-    tx.send(DownloadMessage::Started {
-        content_length: full_content_length,
-    })
-    .await?;
+impl RateTracker {
+    pub fn add(&mut self, length: u64) {
+        self.window_length += length;
 
-    let mut tm = TaskManager::default();
+        let elapsed = self.window_start.elapsed();
+        if elapsed < RATE_WINDOW {
+            return;
+        }
 
-    let (progress_tx, mut progress_rx) = mpsc::channel(32);
-    for ((output_path, response), progress_tx) in
-        responses.into_iter().zip(std::iter::repeat(progress_tx))
-    {
-        tm.tracker.spawn(stream_response_to_file(
-            response,
-            output_path,
-            progress_tx,
-            tm.token.clone(),
-        ));
+        let sample = (self.window_length as f64 / elapsed.as_secs_f64()) as u64;
+        // Smooth out the rate, so the estimate doesn't jump around with every window
+        self.bytes_per_second = match self.bytes_per_second {
+            0 => sample,
+            rate => (rate * 3 + sample) / 4,
+        };
+        self.window_start = Instant::now();
+        self.window_length = 0;
     }
 
-    tm.tracker.close();
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second
+    }
+
+    pub fn eta_secs(&self, remaining_length: u64) -> Option<u64> {
+        (self.bytes_per_second > 0).then(|| remaining_length / self.bytes_per_second)
+    }
+}
+
+/// Progress of one of the files in [download_files].
+struct FileState {
+    file: DownloadFile,
+    name: String,
+    finished_length: u64,
+    total_length: Option<u64>,
+    rate: RateTracker,
+    last_update: Option<Instant>,
+}
+
+impl FileState {
+    fn to_progress(&self) -> FileProgress {
+        FileProgress {
+            url: self.file.url.clone(),
+            name: self.name.clone(),
+            finished_length: self.finished_length,
+            total_length: self.total_length,
+            bytes_per_second: self.rate.bytes_per_second(),
+            eta_secs: self.total_length.and_then(|total_length| {
+                self.rate
+                    .eta_secs(total_length.saturating_sub(self.finished_length))
+            }),
+        }
+    }
+}
+
+/// Validators of a partially downloaded file, stored next to it so the download can be resumed
+/// after a restart, as long as the server still has the same file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PartialDownload {
+    /// The primary URL of the file.
+    url: String,
+
+    /// The mirror the validators below are from.
+    #[serde(default)]
+    source: String,
+
+    #[serde(default)]
+    etag: Option<String>,
+
+    #[serde(default)]
+    last_modified: Option<String>,
+
+    #[serde(default)]
+    total_length: Option<u64>,
+
+    /// Name the server gave the file with `Content-Disposition`, which the finished file is saved as.
+    #[serde(default)]
+    file_name: Option<String>,
+}
+
+impl PartialDownload {
+    fn get_path(part_path: &Path) -> PathBuf {
+        let mut path = part_path.as_os_str().to_owned();
+        path.push(".json");
+        PathBuf::from(path)
+    }
+
+    async fn load(part_path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(Self::get_path(part_path)).await.ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    async fn save(&self, part_path: &Path) -> anyhow::Result<()> {
+        fs::write(Self::get_path(part_path), serde_json::to_string(self)?).await?;
+        Ok(())
+    }
+}
+
+/// A file that is being downloaded from one of its sources.
+#[derive(Debug, Clone)]
+pub struct ActiveDownload {
+    pub file: DownloadFile,
+    /// Client to resume the download with, which may trust the server's own CA certificate.
+    pub client: Client,
+    pub source_index: usize,
+    pub part_path: PathBuf,
+    pub output_path: PathBuf,
+}
+
+enum FileRequest {
+    /// The partial file already has all of its content.
+    Complete,
+    Response {
+        response: Response,
+        offset: u64,
+    },
+}
+
+/// Files are downloaded to a part file keyed by a hash of their URL, and only get their final name
+/// once they are complete. URLs like `download?id=1` and `download?id=2` share a name, but not a part file.
+fn get_part_path(output_dir: &Path, url: &str) -> PathBuf {
+    output_dir.join(format!("{}.part", get_url_key(url)))
+}
+
+/// Short hash of the URL, for files that belong to a download whatever it ends up being named.
+fn get_url_key(url: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    hash[..16].to_string()
+}
+
+/// Final paths of the files in one [download_files] call, so two files that would get the same
+/// name don't overwrite each other.
+#[derive(Debug, Clone, Default)]
+struct ClaimedPaths(Arc<Mutex<HashSet<PathBuf>>>);
+
+impl ClaimedPaths {
+    /// Claims `path` if no other file in the download has it yet.
+    fn try_claim(&self, path: &Path) -> bool {
+        self.0.lock().unwrap().insert(path.to_path_buf())
+    }
+
+    fn release(&self, path: &Path) {
+        self.0.lock().unwrap().remove(path);
+    }
+
+    /// Claims `path`, or else the first `<name> (<n>).<extension>` next to it that is free.
+    /// A file already on disk belongs to another URL, since the URL's own earlier download was either
+    /// reused or removed before its new name is claimed.
+    fn claim(&self, path: PathBuf) -> PathBuf {
+        let mut claimed = self.0.lock().unwrap();
+        if !path.exists() && claimed.insert(path.clone()) {
+            return path;
+        }
 
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy()))
+            .unwrap_or_default();
+
+        let path = (2..)
+            .map(|n| path.with_file_name(format!("{stem} ({n}){extension}")))
+            .find(|path| !claimed.contains(path) && !path.exists())
+            .expect("a free name");
+        tracing::warn!(
+            "Another file is already downloaded to the same name, saving as {}",
+            path.display()
+        );
+        claimed.insert(path.clone());
+        path
+    }
+}
+
+/// Downloads the files into `output_dir`, with at most `max_concurrent` transfers at a time.
+/// Files are only requested once a transfer slot is free.
+pub async fn download_files(
+    files: impl IntoIterator<Item = DownloadFile>,
+    client: Client,
+    output_dir: PathBuf,
+    max_concurrent: usize,
+    tx: mpsc::Sender<DownloadMessage>,
+    token: CancellationToken,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(&output_dir).await?;
+
+    tx.send(DownloadMessage::GatheringData).await?;
+
+    let mut files: Vec<FileState> = files
+        .into_iter()
+        .map(|file| FileState {
+            name: get_filename_from_url(&file.url),
+            file,
+            finished_length: 0,
+            total_length: None,
+            rate: RateTracker::default(),
+            last_update: None,
+        })
+        .collect();
+
+    tx.send(DownloadMessage::Started { content_length: 0 })
+        .await?;
+
+    let mut tm = TaskManager::default();
+    let (progress_tx, mut progress_rx) = mpsc::channel(32);
+    let claimed = ClaimedPaths::default();
+
+    let mut next_index = 0;
+    let mut active = 0;
     let mut progress_length = 0u64;
+    let mut rate = RateTracker::default();
+    let mut last_update = Instant::now();
 
     loop {
-        tokio::select! {
+        while active < max_concurrent.max(1) && next_index < files.len() {
+            tm.tracker.spawn(download_file(
+                next_index,
+                files[next_index].file.clone(),
+                client.clone(),
+                output_dir.clone(),
+                claimed.clone(),
+                progress_tx.clone(),
+                tm.token.clone(),
+            ));
+            next_index += 1;
+            active += 1;
+        }
+
+        if active == 0 {
+            break;
+        }
+
+        let msg = tokio::select! {
             () = token.cancelled() => {
                 tm.shutdown().await;
                 return Err(anyhow!("Download cancelled."));
             },
-            msg = progress_rx.recv() => {
-                if let Some(msg) = msg {
-                    match msg {
-                        FileDownloadMessage::Chunk { chunk_length } => {
-                            progress_length += chunk_length;
-                            tx.send(DownloadMessage::Progress { progress_length }).await?;
-                        },
-                        FileDownloadMessage::Error { path, message } => {
-                            fs::remove_file(path).await?;
-                            tx.send(DownloadMessage::Error { message }).await?;
-                            tm.shutdown().await;
-                            return Err(anyhow!("Download failed."));
-                        },
-                        FileDownloadMessage::Done { path } => {
-                            tx.send(DownloadMessage::FileDone { path }).await?;
-                        }
-                    }
-                } else {
-                    // All senders are done
-                    break;
+            msg = progress_rx.recv() => msg,
+        };
+
+        // All senders are kept alive by this function
+        let Some(msg) = msg else {
+            break;
+        };
+
+        match msg {
+            FileDownloadMessage::Started {
+                index,
+                name,
+                offset,
+                total_length,
+            } => {
+                let state = &mut files[index];
+                state.name = name;
+                state.finished_length = offset;
+                state.total_length = total_length;
+                progress_length += offset;
+            }
+            FileDownloadMessage::Chunk {
+                index,
+                chunk_length,
+            } => {
+                let state = &mut files[index];
+                state.finished_length += chunk_length;
+                state.rate.add(chunk_length);
+                progress_length += chunk_length;
+                rate.add(chunk_length);
+
+                let now = Instant::now();
+                if state
+                    .last_update
+                    .is_none_or(|last_update| now - last_update >= PROGRESS_INTERVAL)
+                {
+                    state.last_update = Some(now);
+                    tx.send(DownloadMessage::FileProgress(state.to_progress()))
+                        .await?;
                 }
+
+                if now - last_update >= PROGRESS_INTERVAL {
+                    last_update = now;
+
+                    let content_length: u64 = files
+                        .iter()
+                        .map(|state| state.total_length.unwrap_or(state.finished_length))
+                        .sum();
+                    // The total is only known once every file has been requested
+                    let all_known = next_index == files.len()
+                        && files.iter().all(|state| state.total_length.is_some());
+
+                    tx.send(DownloadMessage::Progress {
+                        progress_length,
+                        content_length,
+                        bytes_per_second: rate.bytes_per_second(),
+                        eta_secs: all_known
+                            .then(|| rate.eta_secs(content_length.saturating_sub(progress_length)))
+                            .flatten(),
+                    })
+                    .await?;
+                }
+            }
+            FileDownloadMessage::AttemptFailed(failure) => {
+                tx.send(DownloadMessage::AttemptFailed(failure)).await?;
+            }
+            FileDownloadMessage::Error {
+                index,
+                path,
+                message,
+            } => {
+                // The partial file is kept, so the download can be resumed later
+                tracing::warn!(
+                    "Download of {} to '{}' failed: {message}",
+                    files[index].file.url,
+                    path.display()
+                );
+                tx.send(DownloadMessage::Error { message }).await?;
+                tm.shutdown().await;
+                return Err(anyhow!("Download failed."));
+            }
+            FileDownloadMessage::Done { index, path } => {
+                active -= 1;
+
+                let state = &mut files[index];
+                state.total_length = Some(state.finished_length);
+                tx.send(DownloadMessage::FileProgress(state.to_progress()))
+                    .await?;
+                tx.send(DownloadMessage::FileDone {
+                    url: state.file.url.clone(),
+                    path,
+                })
+                .await?;
             }
         }
     }
 
+    tm.tracker.close();
     tm.tracker.wait().await;
 
+    tx.send(DownloadMessage::Progress {
+        progress_length,
+        content_length: progress_length,
+        bytes_per_second: rate.bytes_per_second(),
+        eta_secs: Some(0),
+    })
+    .await?;
     tx.send(DownloadMessage::Done).await?;
 
     Ok(())
 }
 
-pub async fn stream_response_to_file(
-    response: Response,
-    output_path: PathBuf,
+/// Downloads a single file, reporting back to [download_files] with its index.
+async fn download_file(
+    index: usize,
+    file: DownloadFile,
+    client: Client,
+    output_dir: PathBuf,
+    claimed: ClaimedPaths,
     tx: mpsc::Sender<FileDownloadMessage>,
     token: CancellationToken,
 ) {
-    let outer_tx = tx.clone();
-    if let Err(err) = stream_response_to_file_result(response, &output_path, tx, token).await {
-        let _ = outer_tx
+    let part_path = get_part_path(&output_dir, &file.url);
+    if let Err(err) =
+        download_file_result(index, file, &client, &output_dir, &claimed, &tx, token).await
+    {
+        let _ = tx
             .send(FileDownloadMessage::Error {
-                message: format!(
-                    "Error while downloading to file '{}': {err}",
-                    output_path.display()
-                ),
-                path: output_path,
+                index,
+                message: format!("{err:#}"),
+                path: part_path,
             })
             .await;
     }
 }
 
-pub async fn stream_response_to_file_result(
-    response: Response,
-    output_path: &PathBuf,
-    tx: mpsc::Sender<FileDownloadMessage>,
+async fn download_file_result(
+    index: usize,
+    file: DownloadFile,
+    client: &Client,
+    output_dir: &Path,
+    claimed: &ClaimedPaths,
+    tx: &mpsc::Sender<FileDownloadMessage>,
     token: CancellationToken,
 ) -> anyhow::Result<()> {
-    let mut file = File::create(output_path).await?;
+    let part_path = &get_part_path(output_dir, &file.url);
+    // An earlier download of another file in this batch may have the same name
+    let existing_path = find_download_path(output_dir, &file.url)
+        .await
+        .filter(|path| path.exists() && claimed.try_claim(path));
 
-    let mut stream = response.bytes_stream();
+    if let Some(existing_path) = existing_path {
+        // Files from earlier downloads are only reused if they still match the manifest
+        match verify_file(&file, &existing_path).await {
+            Ok(()) => {
+                tx.send(FileDownloadMessage::Done {
+                    index,
+                    path: existing_path,
+                })
+                .await?;
+                return Ok(());
+            }
+            Err(err) => {
+                tracing::warn!("Downloading existing file again: {err:#}");
+                fs::remove_file(&existing_path).await?;
+                claimed.release(&existing_path);
+            }
+        }
+    }
 
-    loop {
-        tokio::select! {
-            () = token.cancelled() => {
-                fs::remove_file(output_path).await?;
+    let (source_index, request) =
+        request_with_failover(&file, client, 0, part_path, tx, &token).await?;
+
+    let (source_index, request) = match request {
+        FileRequest::Complete => match verify_file(&file, part_path).await {
+            Ok(()) => {
+                let filename = get_part_filename(part_path)
+                    .await
+                    .unwrap_or_else(|| get_filename_from_url(&file.url));
+                let output_path = claimed.claim(output_dir.join(filename));
+
+                finish_part(part_path, &output_path).await?;
+                record_download_path(output_dir, &file.url, &output_path).await?;
+                tx.send(FileDownloadMessage::Done {
+                    index,
+                    path: output_path,
+                })
+                .await?;
                 return Ok(());
             }
-            chunk = stream.next() => {
-                if let Some(chunk) = chunk {
-                    let chunk = chunk?;
-                    file.write_all(&chunk).await?;
+            Err(err) => {
+                tracing::warn!("Discarding partial download: {err:#}");
+                discard_part(part_path).await;
+                request_with_failover(&file, client, source_index, part_path, tx, &token).await?
+            }
+        },
+        request => (source_index, request),
+    };
 
-                    tx.send(FileDownloadMessage::Chunk {
-                        chunk_length: chunk.len() as u64,
-                    })
-                    .await?;
-                } else {
-                    break;
+    let FileRequest::Response { response, offset } = request else {
+        return Err(anyhow!("Could not restart download of {}.", file.url));
+    };
+
+    // A resumed response doesn't have to name the file again
+    let filename = match get_part_filename(part_path).await {
+        Some(filename) => filename,
+        None => get_filename_from_response_or_url(&response, &file.url),
+    };
+    let output_path = claimed.claim(output_dir.join(filename));
+    tx.send(FileDownloadMessage::Started {
+        index,
+        name: output_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        offset,
+        total_length: response.content_length().map(|length| offset + length),
+    })
+    .await?;
+
+    let download = ActiveDownload {
+        file,
+        client: client.clone(),
+        source_index,
+        part_path: part_path.to_path_buf(),
+        output_path,
+    };
+
+    let url = download.file.url.clone();
+    let path = stream_response_to_file(index, download, response, offset, tx, token).await?;
+
+    if let Some(path) = path {
+        record_download_path(output_dir, &url, &path).await?;
+        tx.send(FileDownloadMessage::Done { index, path }).await?;
+    }
+
+    Ok(())
+}
+
+/// Requests the file from its sources, starting at `start_source`, retrying transient errors with backoff
+/// before failing over to the next mirror. Returns the index of the source that responded.
+async fn request_with_failover(
+    file: &DownloadFile,
+    client: &Client,
+    start_source: usize,
+    part_path: &Path,
+    tx: &mpsc::Sender<FileDownloadMessage>,
+    token: &CancellationToken,
+) -> anyhow::Result<(usize, FileRequest)> {
+    let sources = file.sources();
+    let mut last_error = None;
+
+    for index in (0..sources.len()).map(|offset| (start_source + offset) % sources.len()) {
+        let source = sources[index];
+
+        for attempt in 1..=ATTEMPTS_PER_SOURCE {
+            let err = match request_file(client, source, &file.url, part_path).await {
+                Ok(request) => return Ok((index, request)),
+                Err(err) => err,
+            };
+
+            let retry_in =
+                (is_transient(&err) && attempt < ATTEMPTS_PER_SOURCE).then(|| retry_delay(attempt));
+
+            tracing::warn!("Request {attempt} for {source} failed: {err:#}");
+            tx.send(FileDownloadMessage::AttemptFailed(AttemptFailure {
+                url: file.url.clone(),
+                source: source.clone(),
+                attempt,
+                message: format!("{err:#}"),
+                retry_in_ms: retry_in.map(|delay| delay.as_millis() as u64),
+            }))
+            .await?;
+
+            last_error = Some(err);
+
+            let Some(retry_in) = retry_in else {
+                break;
+            };
+
+            tokio::select! {
+                () = token.cancelled() => {
+                    return Err(anyhow!("Download cancelled."));
                 }
+                () = tokio::time::sleep(retry_in) => {}
             }
         }
     }
 
-    tx.send(FileDownloadMessage::Done {
-        path: output_path.clone(),
-    })
+    Err(last_error.unwrap_or(anyhow!("No sources to download {} from.", file.url)))
+}
+
+/// Exponential backoff with jitter, so clients that failed at the same time don't all retry at once.
+fn retry_delay(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(RETRY_MAX_DELAY);
+    let half = delay.as_millis() as u64 / 2;
+
+    Duration::from_millis(half + rand::random_range(0..=half))
+}
+
+/// Connection problems and server errors may go away when retried, while other errors won't.
+fn is_transient(err: &anyhow::Error) -> bool {
+    let Some(err) = err.downcast_ref::<reqwest::Error>() else {
+        return false;
+    };
+
+    match err.status() {
+        Some(status) => {
+            status.is_server_error()
+                || status == StatusCode::REQUEST_TIMEOUT
+                || status == StatusCode::TOO_MANY_REQUESTS
+        }
+        None => true,
+    }
+}
+
+/// Requests the file from `source`, continuing after the content of the partial file if it is still the same file.
+async fn request_file(
+    client: &Client,
+    source: &str,
+    url: &str,
+    part_path: &Path,
+) -> anyhow::Result<FileRequest> {
+    let offset = fs::metadata(part_path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or_default();
+    let partial = match offset {
+        0 => None,
+        _ => PartialDownload::load(part_path)
+            .await
+            .filter(|partial| partial.url == url),
+    };
+
+    if let Some(partial) = &partial {
+        let validator = partial
+            .etag
+            .as_ref()
+            .or(partial.last_modified.as_ref())
+            .filter(|_| partial.source == source);
+
+        // Validators only apply to the mirror they came from. Other mirrors are trusted
+        // to have the same file if it has the same length.
+        if validator.is_some() || partial.total_length.is_some() {
+            let mut request = client.get(source).header(RANGE, format!("bytes={offset}-"));
+            if let Some(validator) = validator {
+                request = request.header(IF_RANGE, validator);
+            }
+            let response = request.send().await?;
+
+            match response.status() {
+                StatusCode::PARTIAL_CONTENT => {
+                    let range = parse_content_range(&response);
+                    let start = range.map(|(start, _)| start);
+                    let total_length = range.and_then(|(_, total_length)| total_length);
+
+                    let same_length = match partial.total_length {
+                        Some(expected) => total_length == Some(expected),
+                        None => validator.is_some(),
+                    };
+
+                    if start == Some(offset) && same_length {
+                        tracing::info!(
+                            "Resuming download of {url} from {source} at {offset} bytes"
+                        );
+                        if partial.source != source {
+                            let header = |name: HeaderName| get_header(&response, name);
+                            PartialDownload {
+                                url: url.to_string(),
+                                source: source.to_string(),
+                                etag: header(ETAG),
+                                last_modified: header(LAST_MODIFIED),
+                                total_length: partial.total_length,
+                                file_name: partial.file_name.clone(),
+                            }
+                            .save(part_path)
+                            .await?;
+                        }
+                        return Ok(FileRequest::Response { response, offset });
+                    }
+                    tracing::warn!(
+                        "Unexpected range in response from {source}, restarting download"
+                    );
+                }
+                StatusCode::RANGE_NOT_SATISFIABLE if partial.total_length == Some(offset) => {
+                    return Ok(FileRequest::Complete);
+                }
+                status if status.is_success() => {
+                    // The file changed on the server, and is sent in full
+                    return start_new_part(source, url, response, part_path).await;
+                }
+                status => {
+                    tracing::warn!(
+                        "Could not resume download from {source} ({status}), restarting download"
+                    );
+                }
+            }
+        }
+    }
+
+    let response = client.get(source).send().await?;
+    start_new_part(source, url, response, part_path).await
+}
+
+async fn start_new_part(
+    source: &str,
+    url: &str,
+    response: Response,
+    part_path: &Path,
+) -> anyhow::Result<FileRequest> {
+    let response = response.error_for_status()?;
+
+    PartialDownload {
+        url: url.to_string(),
+        source: source.to_string(),
+        etag: get_header(&response, ETAG),
+        last_modified: get_header(&response, LAST_MODIFIED),
+        total_length: response.content_length(),
+        file_name: get_filename_from_response(&response),
+    }
+    .save(part_path)
     .await?;
 
-    Ok(())
+    Ok(FileRequest::Response {
+        response,
+        offset: 0,
+    })
 }
 
-pub fn get_filename_from_response_or_url(response: &Response, url: &str) -> String {
-    // Try to get the filename from the Content-Disposition header
-    let filename = response
+fn get_header(response: &Response, name: HeaderName) -> Option<String> {
+    response
         .headers()
-        .get("Content-Disposition")
-        .and_then(|header_value| header_value.to_str().ok())
-        .and_then(|header_str| {
-            header_str.split(';').find_map(|part| {
-                let trimmed_part = part.trim();
-                if trimmed_part.starts_with("filename=") {
-                    // Remove "filename=" and any surrounding quotes
-                    let filename_with_quotes = trimmed_part.trim_start_matches("filename=");
-                    let filename_without_quotes = filename_with_quotes
-                        .trim_start_matches('"')
-                        .trim_end_matches('"');
-                    Some(filename_without_quotes.to_string())
-                } else {
-                    None
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+/// Returns the start and total length of a `Content-Range: bytes <start>-<end>/<total>` header.
+fn parse_content_range(response: &Response) -> Option<(u64, Option<u64>)> {
+    let value = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _end) = range.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+/// Name the server gave the file that is being downloaded to the part file, if any.
+async fn get_part_filename(part_path: &Path) -> Option<String> {
+    PartialDownload::load(part_path)
+        .await?
+        .file_name
+        .filter(|name| filename::sanitize(name).is_some())
+}
+
+/// Removes a part file that can't be resumed, along with its metadata.
+async fn discard_part(part_path: &Path) {
+    let _ = fs::remove_file(part_path).await;
+    let _ = fs::remove_file(PartialDownload::get_path(part_path)).await;
+}
+
+/// Checks a file on disk against the size and hash from the manifest.
+pub async fn verify_file(file: &DownloadFile, path: &Path) -> anyhow::Result<()> {
+    if !file.has_expectations() {
+        return Ok(());
+    }
+
+    let length = fs::metadata(path).await?.len();
+    let sha256 = match &file.sha256 {
+        Some(_) => {
+            let mut hasher = Sha256::new();
+            hash_part(path, length, &mut hasher).await?;
+            Some(format!("{:x}", hasher.finalize()))
+        }
+        None => None,
+    };
+
+    file.verify(length, sha256.as_deref())
+}
+
+/// Feeds the first `length` bytes of the file into the hasher, to continue hashing a resumed download.
+async fn hash_part(path: &Path, length: u64, hasher: &mut Sha256) -> anyhow::Result<()> {
+    let mut input = File::open(path).await?.take(length);
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = input.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(())
+}
+
+/// Gives a completely downloaded part file its final name.
+async fn finish_part(part_path: &Path, output_path: &Path) -> anyhow::Result<()> {
+    fs::rename(part_path, output_path).await?;
+    let _ = fs::remove_file(PartialDownload::get_path(part_path)).await;
+    Ok(())
+}
+
+/// Streams the response into the part file starting at `offset`, and returns the path of the finished file,
+/// or `None` if cancelled. When the connection drops, the download is resumed at the current offset,
+/// from the same source or the next mirror.
+pub async fn stream_response_to_file(
+    index: usize,
+    download: ActiveDownload,
+    mut response: Response,
+    offset: u64,
+    tx: &mpsc::Sender<FileDownloadMessage>,
+    token: CancellationToken,
+) -> anyhow::Result<Option<PathBuf>> {
+    let ActiveDownload {
+        file,
+        client,
+        mut source_index,
+        part_path,
+        output_path,
+    } = download;
+
+    let url = file.url.clone();
+    let total_length = response.content_length().map(|length| offset + length);
+
+    if let (Some(expected_size), Some(total_length)) = (file.size, total_length) {
+        if expected_size != total_length {
+            discard_part(&part_path).await;
+            return Err(anyhow!(
+                "{url} has a size of {total_length} bytes, but expected {expected_size} bytes."
+            ));
+        }
+    }
+
+    // The hash covers the whole file, so a resumed download first hashes what was already downloaded
+    let mut hasher = match file.sha256 {
+        Some(_) => {
+            let mut hasher = Sha256::new();
+            if offset > 0 {
+                hash_part(&part_path, offset, &mut hasher).await?;
+            }
+            Some(hasher)
+        }
+        None => None,
+    };
+
+    let mut output = open_part(&part_path, offset).await?;
+    let mut written = offset;
+
+    let mut stalled_interruptions = 0;
+    'download: loop {
+        let interrupted_at = written;
+        let mut stream = response.bytes_stream();
+
+        let error = loop {
+            tokio::select! {
+                () = token.cancelled() => {
+                    // The partial file is kept, so the download can be resumed later
+                    output.flush().await?;
+                    return Ok(None);
                 }
-            })
-        });
-
-    match filename {
-        Some(name) => name,
-        None => {
-            // If Content-Disposition header is not present or doesn't contain filename,
-            // try to extract it from the URL
-            get_filename_from_url(url).unwrap_or("unknown".to_string())
+                chunk = stream.next() => {
+                    match chunk {
+                        Some(Ok(chunk)) => {
+                            tokio::select! {
+                                () = token.cancelled() => {
+                                    output.flush().await?;
+                                    return Ok(None);
+                                }
+                                () = bandwidth::limiter().acquire(chunk.len() as u64) => {}
+                            }
+
+                            output.write_all(&chunk).await?;
+                            written += chunk.len() as u64;
+                            if let Some(hasher) = &mut hasher {
+                                hasher.update(&chunk);
+                            }
+
+                            tx.send(FileDownloadMessage::Chunk {
+                                index,
+                                chunk_length: chunk.len() as u64,
+                            })
+                            .await?;
+                        }
+                        Some(Err(err)) => break err,
+                        None => break 'download,
+                    }
+                }
+            }
+        };
+
+        if written == interrupted_at {
+            stalled_interruptions += 1;
+        } else {
+            stalled_interruptions = 0;
         }
+        if stalled_interruptions > MAX_STALLED_INTERRUPTIONS {
+            return Err(error.into());
+        }
+
+        tracing::warn!("Download of {url} interrupted at {written} bytes: {error}");
+        output.flush().await?;
+
+        let (next_index, request) =
+            request_with_failover(&file, &client, source_index, &part_path, tx, &token).await?;
+        source_index = next_index;
+
+        match request {
+            FileRequest::Complete => break,
+            FileRequest::Response {
+                response: next_response,
+                offset,
+            } => {
+                if offset != written {
+                    // The new part already has the validators of the changed file, so it can't be resumed
+                    discard_part(&part_path).await;
+                    return Err(anyhow!("{url} changed on the server while downloading."));
+                }
+                response = next_response;
+            }
+        }
+    }
+
+    output.flush().await?;
+    drop(output);
+
+    if let Some(total_length) = total_length {
+        if written != total_length {
+            return Err(anyhow!(
+                "Download of {url} ended after {written} of {total_length} bytes."
+            ));
+        }
+    }
+
+    let sha256 = hasher.map(|hasher| format!("{:x}", hasher.finalize()));
+    if let Err(err) = file.verify(written, sha256.as_deref()) {
+        // A corrupt download can't be resumed, so it starts over next time
+        discard_part(&part_path).await;
+        return Err(err);
+    }
+
+    finish_part(&part_path, &output_path).await?;
+
+    Ok(Some(output_path))
+}
+
+async fn open_part(part_path: &Path, offset: u64) -> anyhow::Result<File> {
+    if offset == 0 {
+        return Ok(File::create(part_path).await?);
     }
+
+    Ok(OpenOptions::new().append(true).open(part_path).await?)
 }
 
-fn get_filename_from_url(url: &str) -> Option<String> {
-    url.split('/').last().map(|s| s.to_string())
+/// Returns a safe filename for the download, from the `Content-Disposition` header or the URL.
+pub fn get_filename_from_response_or_url(response: &Response, url: &str) -> String {
+    get_filename_from_response(response).unwrap_or_else(|| get_filename_from_url(url))
+}
+
+fn get_filename_from_response(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(filename::from_content_disposition)
+}
+
+/// Path a file is downloaded to, unless the server names it differently.
+pub fn get_download_path(output_dir: &Path, url: &str) -> PathBuf {
+    output_dir.join(get_filename_from_url(url))
+}
+
+/// File recording the name a download of the URL was saved as.
+fn get_name_record_path(output_dir: &Path, url: &str) -> PathBuf {
+    output_dir.join(format!("{}.name", get_url_key(url)))
+}
+
+/// Path an earlier download of exactly this URL was saved to, if any. A file that merely has the
+/// URL's name isn't enough, since another URL can end in the same name.
+pub async fn find_download_path(output_dir: &Path, url: &str) -> Option<PathBuf> {
+    let name = fs::read_to_string(get_name_record_path(output_dir, url))
+        .await
+        .ok()
+        .filter(|name| filename::sanitize(name).is_some())?;
+
+    Some(output_dir.join(name))
+}
+
+/// Remembers where a finished download was saved, so [find_download_path] can find it again.
+pub async fn record_download_path(output_dir: &Path, url: &str, path: &Path) -> anyhow::Result<()> {
+    let record_path = get_name_record_path(output_dir, url);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(anyhow!("Invalid download path {}", path.display()))?;
+    fs::write(record_path, name).await?;
+    Ok(())
+}
+
+fn get_filename_from_url(url: &str) -> String {
+    filename::from_url(url).unwrap_or("unknown".to_string())
 }
 
 #[cfg(test)]
@@ -227,7 +1118,122 @@ mod tests {
     use tokio::{fs, sync::mpsc};
     use tokio_util::sync::CancellationToken;
 
-    use crate::file_download::{download_files, DownloadMessage};
+    use crate::{
+        file_download::{
+            download_files, find_download_path, get_name_record_path, get_part_path,
+            record_download_path, verify_file, ClaimedPaths, DownloadFile, DownloadMessage,
+        },
+        http,
+    };
+
+    #[tokio::test]
+    pub async fn test_verify_file() {
+        let path = std::env::temp_dir().join("xi-launcher-verify-file-test");
+        fs::write(&path, b"hello").await.unwrap();
+
+        let file = |size: Option<u64>, sha256: &str| DownloadFile {
+            url: "https://example.com/hello".to_string(),
+            size,
+            sha256: Some(sha256.to_string()),
+            ..Default::default()
+        };
+        let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+        assert!(verify_file(&file(Some(5), sha256), &path).await.is_ok());
+        assert!(verify_file(&file(None, &sha256.to_uppercase()), &path)
+            .await
+            .is_ok());
+        assert!(verify_file(&file(Some(6), sha256), &path).await.is_err());
+        assert!(verify_file(&file(None, &"0".repeat(64)), &path)
+            .await
+            .is_err());
+
+        let _ = fs::remove_file(path).await;
+    }
+
+    #[tokio::test]
+    pub async fn test_find_download_path() {
+        let dir = std::env::temp_dir().join("xi-launcher-download-path-test");
+        let _ = fs::remove_dir_all(&dir).await;
+        fs::create_dir_all(&dir).await.unwrap();
+        let url = "https://example.com/download?id=1";
+
+        // A file with the URL's name may belong to another URL ending in the same name
+        fs::write(dir.join("download"), b"").await.unwrap();
+        assert_eq!(find_download_path(&dir, url).await, None);
+
+        // Files named by the server are found again through the recorded name
+        record_download_path(&dir, url, &dir.join("installer.7z"))
+            .await
+            .unwrap();
+        assert_eq!(
+            find_download_path(&dir, url).await,
+            Some(dir.join("installer.7z"))
+        );
+
+        record_download_path(&dir, url, &dir.join("download"))
+            .await
+            .unwrap();
+        assert_eq!(
+            find_download_path(&dir, url).await,
+            Some(dir.join("download"))
+        );
+        assert_eq!(
+            find_download_path(&dir, "https://example.com/download?id=2").await,
+            None
+        );
+
+        fs::write(get_name_record_path(&dir, url), "../outside.7z")
+            .await
+            .unwrap();
+        assert_eq!(find_download_path(&dir, url).await, None);
+
+        let _ = fs::remove_dir_all(dir).await;
+    }
+
+    #[test]
+    pub fn test_part_path() {
+        let dir = std::env::temp_dir();
+        let first = get_part_path(&dir, "https://example.com/download?id=1");
+        let second = get_part_path(&dir, "https://example.com/download?id=2");
+
+        assert_ne!(first, second);
+        assert_eq!(
+            first,
+            get_part_path(&dir, "https://example.com/download?id=1")
+        );
+        assert_eq!(first.extension().unwrap(), "part");
+    }
+
+    #[test]
+    pub fn test_claimed_paths() {
+        let dir = std::env::temp_dir().join("xi-launcher-claimed-paths-test");
+        let claimed = ClaimedPaths::default();
+
+        // Files with the same name are numbered instead of overwriting each other
+        assert_eq!(claimed.claim(dir.join("data.7z")), dir.join("data.7z"));
+        assert_eq!(claimed.claim(dir.join("data.7z")), dir.join("data (2).7z"));
+        assert_eq!(claimed.claim(dir.join("data.7z")), dir.join("data (3).7z"));
+        assert_eq!(claimed.claim(dir.join("download")), dir.join("download"));
+        assert_eq!(
+            claimed.claim(dir.join("download")),
+            dir.join("download (2)")
+        );
+
+        assert!(!claimed.try_claim(&dir.join("data.7z")));
+        claimed.release(&dir.join("data.7z"));
+        assert!(claimed.try_claim(&dir.join("data.7z")));
+
+        // A file on disk was saved for another URL in an earlier download
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("other.7z"), b"").unwrap();
+        assert_eq!(
+            claimed.claim(dir.join("other.7z")),
+            dir.join("other (2).7z")
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 
     #[tokio::test]
     #[ignore]
@@ -237,10 +1243,18 @@ mod tests {
 
         tokio::spawn(download_files(
             [
-                "https://sh.rustup.rs".to_string(),
-                "https://get.pnpm.io/install.ps1".to_string(),
+                DownloadFile {
+                    url: "https://sh.rustup.rs".to_string(),
+                    ..Default::default()
+                },
+                DownloadFile {
+                    url: "https://get.pnpm.io/install.ps1".to_string(),
+                    ..Default::default()
+                },
             ],
+            http::client(),
             out.clone(),
+            2,
             tx,
             CancellationToken::new(),
         ));
@@ -253,10 +1267,20 @@ mod tests {
                 DownloadMessage::Started { content_length } => {
                     eprintln!("Download started of {content_length} bytes")
                 }
-                DownloadMessage::Progress { progress_length } => {
-                    eprintln!("Download progress {progress_length} bytes")
+                DownloadMessage::Progress {
+                    progress_length,
+                    content_length,
+                    ..
+                } => {
+                    eprintln!("Download progress {progress_length}/{content_length} bytes")
+                }
+                DownloadMessage::FileProgress(progress) => {
+                    eprintln!("File progress {progress:?}")
+                }
+                DownloadMessage::AttemptFailed(failure) => {
+                    eprintln!("Attempt failed: {failure:?}")
                 }
-                DownloadMessage::FileDone { path } => {
+                DownloadMessage::FileDone { path, .. } => {
                     eprintln!("File completed: {}", path.display());
                 }
                 DownloadMessage::Done => {
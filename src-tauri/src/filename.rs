@@ -0,0 +1,270 @@
+use percent_encoding::percent_decode_str;
+use reqwest::Url;
+
+/// Names Windows reserves for devices, with or without an extension.
+const RESERVED_WINDOWS_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters that can't be part of a token in a header parameter.
+const TOKEN_SEPARATORS: &str = "()<>@,;:\\\"/[]?={}";
+
+/// Characters that are not allowed in file names on Windows, besides control characters.
+const INVALID_FILENAME_CHARS: &str = "/\\:<>\"|?*";
+
+/// Returns the filename of a `Content-Disposition` header as described in RFC 6266, if it is safe to use.
+/// `filename*` takes precedence over `filename`, as recommended by the RFC.
+pub fn from_content_disposition(header: &str) -> Option<String> {
+    let mut params = split_params(header)?.into_iter();
+
+    // The disposition type must be a token, and is not used otherwise
+    let disposition_type = params.next()?;
+    if !is_token(disposition_type.trim()) {
+        return None;
+    }
+
+    let mut filename = None;
+    let mut extended_filename = None;
+
+    for param in params {
+        let (name, value) = param.split_once('=')?;
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        match name.as_str() {
+            "filename" if filename.is_none() => filename = Some(parse_value(value)?),
+            "filename*" if extended_filename.is_none() => {
+                extended_filename = Some(decode_ext_value(value)?)
+            }
+            "filename" | "filename*" => {
+                // Repeated parameters are ambiguous, so neither is trusted
+                return None;
+            }
+            _ => {}
+        }
+    }
+
+    match extended_filename {
+        Some(name) => sanitize(&name),
+        None => sanitize(&filename?),
+    }
+}
+
+/// Returns the last segment of the URL's path, without any query string or fragment, if it is safe to use.
+pub fn from_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let segment = url.path_segments()?.next_back()?;
+    let name = percent_decode_str(segment).decode_utf8().ok()?;
+    sanitize(&name)
+}
+
+/// Returns the name if it can be used as a single file name on any platform.
+pub fn sanitize(name: &str) -> Option<String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+
+    if name
+        .chars()
+        .any(|c| c.is_control() || INVALID_FILENAME_CHARS.contains(c))
+    {
+        return None;
+    }
+
+    // Windows silently strips these, so the file would end up with a different name
+    if name.ends_with('.') || name.ends_with(' ') || name.starts_with(' ') {
+        return None;
+    }
+
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_WINDOWS_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return None;
+    }
+
+    Some(name.to_string())
+}
+
+/// Splits a header on the `;` between parameters, but not inside quoted strings.
+fn split_params(header: &str) -> Option<Vec<&str>> {
+    let mut params = vec![];
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, c) in header.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                params.push(&header[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if in_quotes {
+        return None;
+    }
+
+    let last = &header[start..];
+    // A trailing `;` is tolerated
+    if !last.trim().is_empty() || params.is_empty() {
+        params.push(last);
+    }
+
+    Some(params)
+}
+
+/// Parses a parameter value, which is either a token or a quoted string.
+fn parse_value(value: &str) -> Option<String> {
+    let Some(quoted) = value.strip_prefix('"') else {
+        return is_token(value).then(|| value.to_string());
+    };
+
+    let quoted = quoted.strip_suffix('"')?;
+    let mut unquoted = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.push(chars.next()?),
+            '"' => return None,
+            c => unquoted.push(c),
+        }
+    }
+
+    Some(unquoted)
+}
+
+/// Decodes an RFC 5987 `charset'language'value`, such as `UTF-8''%e2%82%ac%20rates.exe`.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+
+    if !encoded.chars().all(|c| is_attr_char(c) || c == '%') {
+        return None;
+    }
+
+    let bytes = percent_decode_str(encoded).collect::<Vec<u8>>();
+    if charset.eq_ignore_ascii_case("UTF-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("ISO-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !TOKEN_SEPARATORS.contains(c))
+}
+
+fn is_attr_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(
+            c,
+            '!' | '#' | '$' | '&' | '+' | '-' | '.' | '^' | '_' | '`' | '|' | '~'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_plain_and_quoted_filenames() {
+        assert_eq!(
+            from_content_disposition("attachment; filename=FFXISetup.exe"),
+            Some("FFXISetup.exe".to_string())
+        );
+        assert_eq!(
+            from_content_disposition(r#"attachment; filename="FFXI Setup; part 1.exe""#),
+            Some("FFXI Setup; part 1.exe".to_string())
+        );
+        assert_eq!(
+            from_content_disposition(r#"ATTACHMENT; FILENAME="a \"b\".7z""#),
+            None,
+            "quotes are not allowed in Windows file names"
+        );
+        assert_eq!(
+            from_content_disposition(r#"inline; filename="setup\\.exe""#),
+            None
+        );
+    }
+
+    #[test]
+    fn prefers_extended_filenames() {
+        assert_eq!(
+            from_content_disposition(
+                "attachment; filename=fallback.exe; filename*=UTF-8''%E2%82%AC%20setup.exe"
+            ),
+            Some("€ setup.exe".to_string())
+        );
+        assert_eq!(
+            from_content_disposition("attachment; filename*=iso-8859-1'en'%E9t%E9.zip"),
+            Some("été.zip".to_string())
+        );
+        assert_eq!(
+            from_content_disposition("attachment; filename*=UTF-16''setup.exe"),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_hostile_filenames() {
+        let hostile = [
+            r#"attachment; filename="../../x.exe""#,
+            r#"attachment; filename=..\x.exe"#,
+            r#"attachment; filename="/etc/passwd""#,
+            r#"attachment; filename="C:\Windows\x.exe""#,
+            r#"attachment; filename="C:x.exe""#,
+            "attachment; filename*=UTF-8''..%2F..%2Fx.exe",
+            "attachment; filename*=UTF-8''..%5Cx.exe",
+            "attachment; filename*=UTF-8''x.exe%00.txt",
+            r#"attachment; filename="..""#,
+            r#"attachment; filename=".""#,
+            r#"attachment; filename="""#,
+            r#"attachment; filename="CON""#,
+            r#"attachment; filename="nul.txt""#,
+            r#"attachment; filename="com1 .exe""#,
+            r#"attachment; filename="lpt9.exe""#,
+            r#"attachment; filename="setup.exe.""#,
+            r#"attachment; filename="setup.exe ""#,
+            r#"attachment; filename="setup.exe:evil""#,
+            r#"attachment; filename="unterminated.exe"#,
+            "attachment; filename=a.exe; filename=b.exe",
+            "attachment; filename*=UTF-8''%E2%28%A1.exe",
+            "; filename=setup.exe",
+        ];
+
+        for header in hostile {
+            assert_eq!(from_content_disposition(header), None, "{header}");
+        }
+    }
+
+    #[test]
+    fn filenames_from_urls() {
+        assert_eq!(
+            from_url("https://example.com/files/FFXISetup.exe?token=abc#part"),
+            Some("FFXISetup.exe".to_string())
+        );
+        assert_eq!(
+            from_url("https://example.com/files/FFXI%20Setup.exe"),
+            Some("FFXI Setup.exe".to_string())
+        );
+        assert_eq!(from_url("https://example.com/files/..%2Fx.exe"), None);
+        assert_eq!(from_url("https://example.com/files/"), None);
+        assert_eq!(from_url("https://example.com/aux"), None);
+        assert_eq!(from_url("not a url"), None);
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{LazyLock, RwLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use reqwest::{Certificate, Client, ClientBuilder, NoProxy, Proxy};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::config::profiles::Profile;
+
+const USER_AGENT: &str = concat!("xi-launcher/", env!("CARGO_PKG_VERSION"));
+
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 30;
+
+pub const DEFAULT_READ_TIMEOUT_SECS: u64 = 60;
+
+static CLIENT: LazyLock<RwLock<Client>> = LazyLock::new(|| {
+    let client = build_client(&HttpSettings::default()).unwrap_or_default();
+    RwLock::new(client)
+});
+
+/// Settings the shared client was built with, for building clients with extra certificates.
+static SETTINGS: LazyLock<RwLock<HttpSettings>> = LazyLock::new(Default::default);
+
+/// Settings for every HTTP request the launcher makes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct HttpSettings {
+    #[serde(default)]
+    pub proxy: Option<ProxySettings>,
+
+    /// How long to wait for a connection to be established.
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+
+    /// How long to wait for more data on an open connection, so stalled downloads are retried.
+    #[serde(default)]
+    pub read_timeout_secs: Option<u64>,
+
+    /// PEM files with additional root certificates to trust, for proxies that intercept TLS.
+    #[serde(default)]
+    pub extra_root_certificates: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct ProxySettings {
+    /// Proxy URL, such as `http://proxy.example.com:8080` or `socks5h://localhost:1080`.
+    pub url: String,
+
+    #[serde(default)]
+    pub username: Option<String>,
+
+    #[serde(default)]
+    pub password: Option<String>,
+
+    /// Comma separated hosts that are connected to directly, such as `localhost,.example.com`.
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+}
+
+/// Replaces the shared client with one using the given settings. Keeps the current client if the settings are invalid.
+pub fn configure(settings: &HttpSettings) -> anyhow::Result<()> {
+    let client = build_client(settings)?;
+    *CLIENT.write().unwrap() = client;
+    *SETTINGS.write().unwrap() = settings.clone();
+    Ok(())
+}
+
+/// The shared client for all requests that aren't made to a specific server.
+pub fn client() -> Client {
+    CLIENT.read().unwrap().clone()
+}
+
+/// Client for requests to the server info endpoints, trusting the profile's custom CA certificate if it has one.
+pub fn server_client(profile: &Profile) -> anyhow::Result<Client> {
+    let Some(ca_path) = &profile.server_ca_certificate else {
+        return Ok(client());
+    };
+
+    // Clients can't be extended, so this one is built from the current settings again
+    let settings = SETTINGS.read().unwrap().clone();
+    let builder = add_root_certificates(client_builder(&settings)?, ca_path)?;
+
+    Ok(builder.build()?)
+}
+
+fn build_client(settings: &HttpSettings) -> anyhow::Result<Client> {
+    Ok(client_builder(settings)?.build()?)
+}
+
+fn client_builder(settings: &HttpSettings) -> anyhow::Result<ClientBuilder> {
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(
+            settings
+                .connect_timeout_secs
+                .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+        ))
+        .read_timeout(Duration::from_secs(
+            settings
+                .read_timeout_secs
+                .unwrap_or(DEFAULT_READ_TIMEOUT_SECS),
+        ));
+
+    if let Some(proxy_settings) = &settings.proxy {
+        let mut proxy = Proxy::all(&proxy_settings.url)
+            .with_context(|| format!("Invalid proxy URL {}", proxy_settings.url))?;
+
+        if let Some(username) = &proxy_settings.username {
+            proxy = proxy.basic_auth(
+                username,
+                proxy_settings.password.as_deref().unwrap_or_default(),
+            );
+        }
+        if let Some(no_proxy) = &proxy_settings.no_proxy {
+            proxy = proxy.no_proxy(NoProxy::from_string(no_proxy));
+        }
+
+        builder = builder.proxy(proxy);
+    }
+
+    for path in &settings.extra_root_certificates {
+        builder = add_root_certificates(builder, path)?;
+    }
+
+    Ok(builder)
+}
+
+fn add_root_certificates(mut builder: ClientBuilder, path: &Path) -> anyhow::Result<ClientBuilder> {
+    let pem = std::fs::read(path)
+        .with_context(|| format!("Could not read CA certificate {}", path.display()))?;
+
+    for certificate in Certificate::from_pem_bundle(&pem)
+        .with_context(|| format!("Invalid CA certificate {}", path.display()))?
+    {
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    Ok(builder)
+}
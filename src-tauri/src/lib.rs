@@ -1,12 +1,28 @@
 mod ashita;
+mod bandwidth;
 mod check_game;
+mod client_version;
 mod commands;
 mod config;
+mod convert;
+mod diagnose;
+mod download_cache;
+mod extract;
 mod file_download;
+mod filename;
+mod http;
+mod manifest_cache;
+pub mod manifest_generator;
+mod news;
+mod readiness;
+mod server_directory;
+mod server_status;
+mod signature;
 mod state;
 mod task_manager;
 mod tasks;
 mod util;
+mod version;
 mod windower;
 
 use state::AppStateData;
@@ -26,12 +42,29 @@ pub fn run() {
         commands::delete_profile,
         commands::duplicate_profile,
         commands::move_profile,
+        commands::convert_profile,
+        commands::get_server_list_urls,
+        commands::save_server_list_urls,
+        commands::get_bandwidth_settings,
+        commands::save_bandwidth_settings,
+        commands::get_http_settings,
+        commands::save_http_settings,
+        commands::get_server_directory,
+        commands::create_profile_from_directory,
         commands::should_request_password,
         commands::check_launch_profile,
+        commands::refresh_launch_profile,
+        commands::get_readiness_report,
+        commands::get_server_status,
+        commands::diagnose_server,
+        commands::get_server_news,
+        commands::mark_server_news_read,
         commands::install_game_for_profile,
         commands::update_profile_server_files,
+        commands::pin_component_version,
         commands::launch_profile,
         commands::cancel_possible_profile_task,
+        commands::prune_download_cache,
         commands::list_ashita_addons,
         commands::list_ashita_plugins,
         commands::configure_gamepad,
@@ -47,6 +80,9 @@ pub fn run() {
         specta_builder
             .export(config, "../src/bindings.ts")
             .expect("Failed to export typescript bindings");
+
+        check_game::export_manifest_schemas(std::path::Path::new("../schemas"))
+            .expect("Failed to export server manifest schemas");
     }
 
     // Allow unused mut, since mutable is necessary during release
@@ -69,12 +105,29 @@ pub fn run() {
             commands::delete_profile,
             commands::duplicate_profile,
             commands::move_profile,
+            commands::convert_profile,
+            commands::get_server_list_urls,
+            commands::save_server_list_urls,
+            commands::get_bandwidth_settings,
+            commands::save_bandwidth_settings,
+            commands::get_http_settings,
+            commands::save_http_settings,
+            commands::get_server_directory,
+            commands::create_profile_from_directory,
             commands::should_request_password,
             commands::check_launch_profile,
+            commands::refresh_launch_profile,
+            commands::get_readiness_report,
+            commands::get_server_status,
+            commands::diagnose_server,
+            commands::get_server_news,
+            commands::mark_server_news_read,
             commands::install_game_for_profile,
             commands::update_profile_server_files,
+            commands::pin_component_version,
             commands::launch_profile,
             commands::cancel_possible_profile_task,
+            commands::prune_download_cache,
             commands::list_ashita_addons,
             commands::list_ashita_plugins,
             commands::configure_gamepad,
@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Server manifests keyed by their full URL, so servers sharing a name can't collide.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManifestCache {
+    #[serde(default)]
+    pub entries: BTreeMap<String, CachedManifest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedManifest {
+    pub body: String,
+
+    /// Minisign signature of the body, if the server signs its manifests.
+    #[serde(default)]
+    pub signature: Option<String>,
+
+    #[serde(default)]
+    pub etag: Option<String>,
+
+    #[serde(default)]
+    pub last_modified: Option<String>,
+
+    /// Unix timestamp of when the manifest was last confirmed by the server.
+    pub fetched_at: u64,
+}
+
+pub const MANIFEST_CACHE_FILENAME: &'static str = "manifest_cache.json";
+
+impl ManifestCache {
+    pub fn get_path(dir: &PathBuf) -> PathBuf {
+        dir.join(MANIFEST_CACHE_FILENAME)
+    }
+
+    pub fn save(&self, dir: &PathBuf) -> anyhow::Result<()> {
+        let file = File::create(Self::get_path(dir))?;
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// Returns the cached manifest if it was confirmed by the server within `max_age`.
+    pub fn get_fresh(&self, url: &str, max_age: Duration) -> Option<&CachedManifest> {
+        self.entries
+            .get(url)
+            .filter(|entry| now().saturating_sub(entry.fetched_at) < max_age.as_secs())
+    }
+
+    pub fn insert(
+        &mut self,
+        url: String,
+        body: String,
+        signature: Option<String>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    ) {
+        self.entries.insert(
+            url,
+            CachedManifest {
+                body,
+                signature,
+                etag,
+                last_modified,
+                fetched_at: now(),
+            },
+        );
+    }
+
+    /// Marks the cached manifest as confirmed by the server, after a `304 Not Modified` response.
+    pub fn touch(&mut self, url: &str) -> Option<&CachedManifest> {
+        let entry = self.entries.get_mut(url)?;
+        entry.fetched_at = now();
+        Some(entry)
+    }
+
+    /// Takes over the entries of a copy of the cache that were confirmed more recently than the ones in this cache.
+    pub fn merge(&mut self, other: ManifestCache) {
+        for (url, entry) in other.entries {
+            match self.entries.get(&url) {
+                Some(existing) if existing.fetched_at > entry.fetched_at => {}
+                _ => {
+                    self.entries.insert(url, entry);
+                }
+            }
+        }
+    }
+
+    /// Marks all cached manifests below the given server info URL as stale, so the server is asked for them again.
+    /// They are kept for the conditional request, and for when the server can't be reached.
+    pub fn mark_stale(&mut self, base_url: &str) {
+        let prefix = format!("{}/", base_url.trim_end_matches('/'));
+        for (_, entry) in self
+            .entries
+            .iter_mut()
+            .filter(|(url, _)| url.starts_with(&prefix))
+        {
+            entry.fetched_at = 0;
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_manifests_are_kept() {
+        let mut cache = ManifestCache::default();
+        for url in [
+            "https://a.example.com/versions",
+            "https://b.example.com/versions",
+        ] {
+            cache.insert(
+                url.to_string(),
+                "{}".to_string(),
+                None,
+                Some("\"etag\"".to_string()),
+                None,
+            );
+        }
+
+        cache.mark_stale("https://a.example.com/");
+
+        let max_age = Duration::from_secs(60);
+        assert!(cache
+            .get_fresh("https://a.example.com/versions", max_age)
+            .is_none());
+        assert!(cache
+            .get_fresh("https://b.example.com/versions", max_age)
+            .is_some());
+
+        // The entry can still be revalidated, or used while the server is unreachable
+        let stale = &cache.entries["https://a.example.com/versions"];
+        assert_eq!(stale.etag.as_deref(), Some("\"etag\""));
+        assert_eq!(stale.body, "{}");
+    }
+}
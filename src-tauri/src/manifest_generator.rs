@@ -0,0 +1,607 @@
+//! Generates the `/versions` and `/install` manifests a private server provides to the launcher,
+//! and can serve them along with the files they point to for local testing.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context};
+use sha2::{Digest, Sha256};
+
+use crate::check_game::{DownloadInfo, FileInstallConfig, VersionInfo, VersionsInfo};
+
+const USAGE: &str = "\
+Usage: xi-manifest-generator <generate|serve> [options]
+
+Options:
+  --out <dir>                 Directory to write the manifests and files to (default: xi-server)
+  --base-url <url>            URL the output directory is served at (default: http://127.0.0.1:8080)
+  --dats <dir>                DATs to pack, laid out like the game directory (ROM/..., ROM2/...)
+  --dats-version <version>    Defaults to the previous version if the DATs are unchanged, or the current time
+  --bootloader <file>         xiloader.exe to provide
+  --bootloader-version <version>
+  --installer <file>          Installer part, repeated in install order
+  --listen <addr>             Address to serve on with `serve` (default: 127.0.0.1:8080)
+
+Set the server info address of a profile to the base URL to use the generated manifests.
+To sign them, run `minisign -Sm` on every file in `files` and generate again, so the signatures
+are added to the manifests. Then run `minisign -Sm` on the `versions` and `install` files.
+Signatures next to the --bootloader and --installer files are copied along with them.";
+
+const FILES_DIR: &str = "files";
+const DATS_ARCHIVE_NAME: &str = "dats.7z";
+/// Records the hash of the DATs the archive was packed from, next to the manifests.
+const DATS_SOURCE_NAME: &str = ".dats-source";
+const BOOTLOADER_NAME: &str = "xiloader.exe";
+
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    pub out_dir: PathBuf,
+    pub base_url: String,
+    pub dats: Option<PathBuf>,
+    pub dats_version: Option<String>,
+    pub bootloader: Option<PathBuf>,
+    pub bootloader_version: Option<String>,
+    pub installer_parts: Vec<PathBuf>,
+    pub listen: String,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            out_dir: PathBuf::from("xi-server"),
+            base_url: "http://127.0.0.1:8080".to_string(),
+            dats: None,
+            dats_version: None,
+            bootloader: None,
+            bootloader_version: None,
+            installer_parts: vec![],
+            listen: "127.0.0.1:8080".to_string(),
+        }
+    }
+}
+
+/// Entry point of the `xi-manifest-generator` binary.
+pub fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let command = args.next().unwrap_or_default();
+
+    let mut options = GeneratorOptions::default();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(anyhow!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--out" => options.out_dir = value()?.into(),
+            "--base-url" => options.base_url = value()?,
+            "--dats" => options.dats = Some(value()?.into()),
+            "--dats-version" => options.dats_version = Some(value()?),
+            "--bootloader" => options.bootloader = Some(value()?.into()),
+            "--bootloader-version" => options.bootloader_version = Some(value()?),
+            "--installer" => options.installer_parts.push(value()?.into()),
+            "--listen" => options.listen = value()?,
+            _ => return Err(anyhow!("Unknown option {arg}\n\n{USAGE}")),
+        }
+    }
+
+    match command.as_str() {
+        "generate" => generate(&options),
+        "serve" => {
+            generate(&options)?;
+            serve(&options.out_dir, &options.listen)
+        }
+        _ => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    }
+}
+
+/// Writes the manifests and the files they point to into the output directory.
+pub fn generate(options: &GeneratorOptions) -> anyhow::Result<()> {
+    let files_dir = options.out_dir.join(FILES_DIR);
+    fs::create_dir_all(&files_dir)
+        .with_context(|| format!("Could not create {}", files_dir.display()))?;
+
+    let base_url = options.base_url.trim_end_matches('/');
+    let file_url = |name: &str| format!("{base_url}/{FILES_DIR}/{name}");
+
+    let versions_path = options.out_dir.join("versions");
+    let previous: Option<VersionsInfo> = fs::read_to_string(&versions_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+
+    let mut versions_info = VersionsInfo {
+        dats: None,
+        bootloader: None,
+        components: vec![],
+        client: previous
+            .as_ref()
+            .and_then(|previous| previous.client.clone()),
+    };
+
+    if let Some(dats_dir) = &options.dats {
+        let archive_path = files_dir.join(DATS_ARCHIVE_NAME);
+
+        // Packing the same DATs again gives a different archive, which would look like a new version
+        let source_path = options.out_dir.join(DATS_SOURCE_NAME);
+        let source_hash = hash_tree(dats_dir)?;
+        let packed_hash = fs::read_to_string(&source_path).ok();
+        if archive_path.is_file() && packed_hash.as_deref() == Some(source_hash.as_str()) {
+            println!(
+                "{} is unchanged, keeping {}",
+                dats_dir.display(),
+                archive_path.display()
+            );
+        } else {
+            println!(
+                "Packing {} into {}",
+                dats_dir.display(),
+                archive_path.display()
+            );
+            sevenz_rust::compress_to_path(dats_dir, &archive_path)
+                .with_context(|| format!("Could not pack {}", dats_dir.display()))?;
+            fs::write(&source_path, &source_hash)
+                .with_context(|| format!("Could not write {}", source_path.display()))?;
+        }
+
+        versions_info.dats = Some(describe_file(
+            None,
+            &archive_path,
+            file_url(DATS_ARCHIVE_NAME),
+            options.dats_version.as_ref(),
+            previous
+                .as_ref()
+                .and_then(|previous| previous.dats.as_ref()),
+        )?);
+    }
+
+    if let Some(bootloader) = &options.bootloader {
+        let bootloader_path = files_dir.join(BOOTLOADER_NAME);
+        fs::copy(bootloader, &bootloader_path)
+            .with_context(|| format!("Could not copy {}", bootloader.display()))?;
+
+        versions_info.bootloader = Some(describe_file(
+            Some(bootloader),
+            &bootloader_path,
+            file_url(BOOTLOADER_NAME),
+            options.bootloader_version.as_ref(),
+            previous
+                .as_ref()
+                .and_then(|previous| previous.bootloader.as_ref()),
+        )?);
+    }
+
+    let install_path = options.out_dir.join("install");
+    let previous_download_info: DownloadInfo = fs::read_to_string(&install_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    let mut download_info: DownloadInfo = vec![];
+    let is_split = options.installer_parts.len() > 1;
+    for (index, part) in options.installer_parts.iter().enumerate() {
+        let name = part
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(anyhow!("Invalid installer part {}", part.display()))?;
+
+        let part_path = files_dir.join(name);
+        fs::copy(part, &part_path).with_context(|| format!("Could not copy {}", part.display()))?;
+
+        let url = file_url(name);
+        let (size, sha256) = hash_file(&part_path)?;
+        let previous_sha256 = previous_download_info
+            .iter()
+            .find(|file| file.url == url)
+            .and_then(|file| file.sha256.as_deref());
+        let signature = take_signature(Some(part), &part_path, &sha256, previous_sha256)?;
+
+        download_info.push(FileInstallConfig {
+            url,
+            mirrors: vec![],
+            size: Some(size),
+            sha256: Some(sha256),
+            signature,
+            part: is_split.then_some(index as u32 + 1),
+        });
+    }
+
+    write_json(&versions_path, &versions_info)?;
+    if !download_info.is_empty() {
+        write_json(&install_path, &download_info)?;
+    }
+
+    Ok(())
+}
+
+/// Describes a file in the versions manifest. Keeps the previous version if the file is unchanged,
+/// so regenerating doesn't make every player update again.
+fn describe_file(
+    source: Option<&PathBuf>,
+    path: &Path,
+    url: String,
+    version: Option<&String>,
+    previous: Option<&VersionInfo>,
+) -> anyhow::Result<VersionInfo> {
+    let (size, sha256) = hash_file(path)?;
+    let previous_sha256 = previous.and_then(|previous| previous.sha256.as_deref());
+    let signature = take_signature(source.map(PathBuf::as_path), path, &sha256, previous_sha256)?;
+
+    let version = match (version, previous) {
+        (Some(version), _) => version.clone(),
+        (None, Some(previous)) if previous.sha256.as_ref() == Some(&sha256) => {
+            previous.version.clone()
+        }
+        (None, _) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default()
+            .to_string(),
+    };
+
+    println!("{url}: version {version}, {size} bytes, sha256 {sha256}");
+
+    Ok(VersionInfo {
+        url,
+        version,
+        size: Some(size),
+        sha256: Some(sha256),
+        signature,
+    })
+}
+
+/// Reads the signature of a file in the output directory from `<file>.minisig`, after copying it
+/// from next to the source file if there is one. A signature left over from a previous version of
+/// the file is removed instead, since the launcher would reject it.
+fn take_signature(
+    source: Option<&Path>,
+    path: &Path,
+    sha256: &str,
+    previous_sha256: Option<&str>,
+) -> anyhow::Result<Option<String>> {
+    let signature_file = signature_path(path);
+
+    if let Some(source_signature) = source
+        .map(signature_path)
+        .filter(|source_signature| source_signature.is_file())
+    {
+        fs::copy(&source_signature, &signature_file)
+            .with_context(|| format!("Could not copy {}", source_signature.display()))?;
+    } else if signature_file.is_file() && previous_sha256 != Some(sha256) {
+        println!(
+            "Removing {}, since it signs a previous version of the file",
+            signature_file.display()
+        );
+        fs::remove_file(&signature_file)?;
+    }
+
+    if !signature_file.is_file() {
+        return Ok(None);
+    }
+
+    let signature = fs::read_to_string(&signature_file)
+        .with_context(|| format!("Could not read {}", signature_file.display()))?;
+    Ok(Some(signature))
+}
+
+fn signature_path(path: &Path) -> PathBuf {
+    let mut signature_path = path.as_os_str().to_owned();
+    signature_path.push(".minisig");
+    PathBuf::from(signature_path)
+}
+
+fn hash_file(path: &Path) -> anyhow::Result<(u64, String)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher)?;
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+/// Hashes the paths and contents of every file in the directory.
+fn hash_tree(dir: &Path) -> anyhow::Result<String> {
+    let mut files = vec![];
+    list_files(dir, Path::new(""), &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for relative_path in files {
+        let name = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+
+        let path = dir.join(&relative_path);
+        let mut file =
+            File::open(&path).with_context(|| format!("Could not read {}", path.display()))?;
+        let size = io::copy(&mut file, &mut hasher)?;
+        hasher.update(size.to_le_bytes());
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn list_files(dir: &Path, relative_dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let read_dir =
+        fs::read_dir(dir).with_context(|| format!("Could not read {}", dir.display()))?;
+    for entry in read_dir {
+        let entry = entry?;
+        let relative_path = relative_dir.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            list_files(&entry.path(), &relative_path, files)?;
+        } else {
+            files.push(relative_path);
+        }
+    }
+
+    Ok(())
+}
+
+fn write_json<T: serde::Serialize>(path: &Path, value: &T) -> anyhow::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(value)? + "\n")
+        .with_context(|| format!("Could not write {}", path.display()))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// Serves the output directory over plain HTTP, with support for `HEAD` and resumable `Range` requests.
+pub fn serve(root: &Path, listen: &str) -> anyhow::Result<()> {
+    let listener =
+        TcpListener::bind(listen).with_context(|| format!("Could not listen on {listen}"))?;
+    println!("Serving {} at http://{listen}", root.display());
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+
+        let root = root.to_path_buf();
+        std::thread::spawn(move || {
+            if let Err(err) = handle_connection(&root, stream) {
+                eprintln!("Request failed: {err:#}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(root: &Path, mut stream: TcpStream) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut range = None;
+    let mut if_range = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("range") {
+                range = Some(value.trim().to_string());
+            } else if name.trim().eq_ignore_ascii_case("if-range") {
+                if_range = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    println!("{method} {target}");
+
+    if method != "GET" && method != "HEAD" {
+        return write_status(&mut stream, "405 Method Not Allowed");
+    }
+
+    let Some(path) = resolve_path(root, &target).filter(|path| path.is_file()) else {
+        return write_status(&mut stream, "404 Not Found");
+    };
+
+    let mut file = File::open(&path)?;
+    let metadata = file.metadata()?;
+    let length = metadata.len();
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_secs())
+        .unwrap_or_default();
+    let etag = format!("\"{length:x}-{modified:x}\"");
+
+    // Ranges of a file that changed since are ignored, and the whole file is sent instead
+    if if_range.is_some_and(|if_range| if_range != etag) {
+        range = None;
+    }
+
+    let (status, start, end) = match range
+        .as_deref()
+        .and_then(|range| parse_range(range, length))
+    {
+        Some((start, end)) => ("206 Partial Content", start, end),
+        None if range.is_some() => {
+            return write_status(&mut stream, "416 Range Not Satisfiable");
+        }
+        None => ("200 OK", 0, length.saturating_sub(1)),
+    };
+    let content_length = if length == 0 { 0 } else { end - start + 1 };
+
+    let content_type = match path.extension().and_then(|ext| ext.to_str()) {
+        None => "application/json",
+        Some("minisig") => "text/plain",
+        Some(_) => "application/octet-stream",
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {content_length}\r\nETag: {etag}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n"
+    )?;
+    if status.starts_with("206") {
+        write!(stream, "Content-Range: bytes {start}-{end}/{length}\r\n")?;
+    }
+    write!(stream, "\r\n")?;
+
+    if method == "GET" {
+        file.seek(SeekFrom::Start(start))?;
+        io::copy(&mut file.take(content_length), &mut stream)?;
+    }
+
+    Ok(())
+}
+
+fn write_status(stream: &mut TcpStream, status: &str) -> anyhow::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    )?;
+    Ok(())
+}
+
+/// Maps a request target onto the served directory, refusing anything outside of it.
+fn resolve_path(root: &Path, target: &str) -> Option<PathBuf> {
+    let path = target.split(['?', '#']).next()?.trim_start_matches('/');
+    let relative = Path::new(path);
+
+    if relative
+        .components()
+        .any(|part| !matches!(part, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    Some(root.join(relative))
+}
+
+/// Parses a single `bytes=start-end` range, returning inclusive offsets.
+fn parse_range(range: &str, length: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    let start: u64 = start.trim().parse().ok()?;
+    let end = match end.trim() {
+        "" => length.checked_sub(1)?,
+        end => end.parse::<u64>().ok()?.min(length.checked_sub(1)?),
+    };
+
+    (start <= end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve_path() {
+        let root = Path::new("served");
+        assert_eq!(
+            resolve_path(root, "/files/dats.7z?v=1"),
+            Some(root.join("files/dats.7z"))
+        );
+        assert_eq!(resolve_path(root, "/../secret"), None);
+        assert_eq!(resolve_path(root, "/files/../../secret"), None);
+        assert_eq!(
+            resolve_path(root, "//etc/passwd"),
+            Some(root.join("etc/passwd"))
+        );
+    }
+
+    #[test]
+    fn test_signatures() {
+        let dir = std::env::temp_dir().join("xi-launcher-manifest-generator-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let bootloader = dir.join("xiloader.exe");
+        fs::write(&bootloader, b"bootloader").unwrap();
+        fs::write(signature_path(&bootloader), "untrusted comment: bootloader").unwrap();
+
+        let options = GeneratorOptions {
+            out_dir: dir.join("out"),
+            bootloader: Some(bootloader.clone()),
+            bootloader_version: Some("1".to_string()),
+            ..Default::default()
+        };
+        generate(&options).unwrap();
+
+        let read_versions = || -> VersionsInfo {
+            serde_json::from_str(&fs::read_to_string(options.out_dir.join("versions")).unwrap())
+                .unwrap()
+        };
+        assert_eq!(
+            read_versions().bootloader.unwrap().signature.as_deref(),
+            Some("untrusted comment: bootloader")
+        );
+
+        // Signed in the output directory after generating, and generated again
+        fs::remove_file(signature_path(&bootloader)).unwrap();
+        let signed = signature_path(&options.out_dir.join(FILES_DIR).join(BOOTLOADER_NAME));
+        fs::write(&signed, "untrusted comment: signed").unwrap();
+        generate(&options).unwrap();
+        assert_eq!(
+            read_versions().bootloader.unwrap().signature.as_deref(),
+            Some("untrusted comment: signed")
+        );
+
+        // The signature of the previous bootloader doesn't sign the changed one
+        fs::write(&bootloader, b"changed bootloader").unwrap();
+        generate(&options).unwrap();
+        assert_eq!(read_versions().bootloader.unwrap().signature, None);
+        assert!(!signed.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unchanged_dats() {
+        let dir = std::env::temp_dir().join("xi-launcher-manifest-generator-dats-test");
+        let _ = fs::remove_dir_all(&dir);
+        let dats = dir.join("dats");
+        fs::create_dir_all(dats.join("ROM/0")).unwrap();
+        fs::write(dats.join("ROM/0/0.DAT"), b"dat").unwrap();
+
+        let options = GeneratorOptions {
+            out_dir: dir.join("out"),
+            dats: Some(dats.clone()),
+            ..Default::default()
+        };
+        let read_dats = || -> VersionInfo {
+            let content = fs::read_to_string(options.out_dir.join("versions")).unwrap();
+            serde_json::from_str::<VersionsInfo>(&content)
+                .unwrap()
+                .dats
+                .unwrap()
+        };
+
+        generate(&options).unwrap();
+        let first = read_dats();
+
+        // The same DATs keep their version, even though packing them again would change the archive
+        generate(&options).unwrap();
+        let second = read_dats();
+        assert_eq!(second.version, first.version);
+        assert_eq!(second.sha256, first.sha256);
+
+        fs::write(dats.join("ROM/0/0.DAT"), b"changed dat").unwrap();
+        generate(&options).unwrap();
+        assert_ne!(read_dats().sha256, first.sha256);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-", 10), Some((0, 9)));
+        assert_eq!(parse_range("bytes=5-100", 10), Some((5, 9)));
+        assert_eq!(parse_range("bytes=10-", 10), None);
+        assert_eq!(parse_range("items=0-1", 10), None);
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::{config::profiles::Profile, http, util::load_json_or_default};
+
+/// Response of the optional `/news` endpoint on the server info address.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct NewsFeed {
+    #[serde(default)]
+    pub items: Vec<NewsItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct NewsItem {
+    pub id: String,
+    pub title: String,
+
+    #[serde(default)]
+    pub body: Option<String>,
+
+    /// For example "maintenance" or "patch".
+    #[serde(default)]
+    pub kind: Option<String>,
+
+    #[serde(default)]
+    pub published_at: Option<String>,
+
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ServerNews {
+    pub items: Vec<ServerNewsItem>,
+    pub unread_count: u32,
+
+    /// Unix timestamp of when the news was last fetched successfully.
+    pub fetched_at: Option<u64>,
+
+    /// Set when the latest fetch failed, and the items come from the local cache.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ServerNewsItem {
+    pub item: NewsItem,
+    pub read: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct NewsCache {
+    #[serde(default)]
+    items: Vec<NewsItem>,
+
+    #[serde(default)]
+    read: BTreeSet<String>,
+
+    #[serde(default)]
+    fetched_at: Option<u64>,
+}
+
+impl NewsCache {
+    fn get_path(local_data_dir: &PathBuf, profile: &Profile) -> PathBuf {
+        let server_key: String = profile
+            .get_server_info_addr()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+
+        local_data_dir.join(format!("news/{server_key}.json"))
+    }
+
+    fn save(&self, path: &PathBuf) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = File::create(path)?;
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    fn to_server_news(&self, error: Option<String>) -> ServerNews {
+        let items: Vec<ServerNewsItem> = self
+            .items
+            .iter()
+            .map(|item| ServerNewsItem {
+                read: self.read.contains(&item.id),
+                item: item.clone(),
+            })
+            .collect();
+
+        ServerNews {
+            unread_count: items.iter().filter(|item| !item.read).count() as u32,
+            items,
+            fetched_at: self.fetched_at,
+            error,
+        }
+    }
+}
+
+/// Fetches the news of the profile's server, falling back to the cached news when it can't be reached.
+pub async fn get_server_news(
+    local_data_dir: &PathBuf,
+    profile: &Profile,
+) -> anyhow::Result<ServerNews> {
+    let cache_path = NewsCache::get_path(local_data_dir, profile);
+    let mut cache: NewsCache = load_json_or_default(&cache_path);
+
+    let error = match fetch_news(profile).await {
+        Ok(feed) => {
+            // Forget read markers of items that are no longer published
+            cache
+                .read
+                .retain(|id| feed.items.iter().any(|item| &item.id == id));
+            cache.items = feed.items;
+            cache.fetched_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|duration| duration.as_secs());
+            cache.save(&cache_path)?;
+            None
+        }
+        Err(err) => {
+            tracing::warn!("Could not fetch server news: {err:?}");
+            Some(format!("{err:#}"))
+        }
+    };
+
+    Ok(cache.to_server_news(error))
+}
+
+pub fn mark_news_read(
+    local_data_dir: &PathBuf,
+    profile: &Profile,
+    item_ids: Vec<String>,
+) -> anyhow::Result<ServerNews> {
+    let cache_path = NewsCache::get_path(local_data_dir, profile);
+    let mut cache: NewsCache = load_json_or_default(&cache_path);
+
+    cache.read.extend(item_ids);
+    cache.save(&cache_path)?;
+
+    Ok(cache.to_server_news(None))
+}
+
+async fn fetch_news(profile: &Profile) -> anyhow::Result<NewsFeed> {
+    let response = http::server_client(profile)?
+        .get(profile.get_server_info_endpoint("news")?)
+        .send()
+        .await?;
+
+    // The news endpoint is optional
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(NewsFeed::default());
+    }
+
+    Ok(response.error_for_status()?.json().await?)
+}
@@ -0,0 +1,639 @@
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use serde::Serialize;
+use specta::Type;
+
+use crate::{
+    ashita,
+    check_game::{get_component_change, get_versions_info, needs_password, ServerRequestError},
+    client_version::{check_ashita_version, detect_ashita_version, detect_client_version},
+    config::profiles::{AuthKind, OfflinePolicy, Profile},
+    manifest_cache::ManifestCache,
+    state::AppState,
+    version::VersionChange,
+};
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ReadinessReport {
+    /// Whether none of the findings are errors.
+    pub ready: bool,
+    pub findings: Vec<ReadinessFinding>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ReadinessFinding {
+    pub check: ReadinessCheck,
+    pub severity: Severity,
+    pub message: String,
+    pub fix: Option<FixAction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Type)]
+pub enum ReadinessCheck {
+    GameDirectory,
+    Frontend,
+    Bootloader,
+    ServerFiles,
+    ClientVersion,
+    PivotPlugin,
+    Credentials,
+    ScriptFile,
+    ConfigWriteable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Type)]
+pub enum Severity {
+    Ok,
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Type)]
+pub enum FixAction {
+    SetGameDirectory,
+    InstallGame,
+    UpdateClient,
+    SetAshitaDirectory,
+    SetWindowerDirectory,
+    SetWindowerProfile,
+    UpdateServerFiles,
+    InstallPivot,
+    SetAccountName,
+    EnterPassword,
+    SaveProfile,
+    CheckPermissions,
+}
+
+struct Findings(Vec<ReadinessFinding>);
+
+impl Findings {
+    fn add(
+        &mut self,
+        check: ReadinessCheck,
+        severity: Severity,
+        message: impl Into<String>,
+        fix: Option<FixAction>,
+    ) {
+        self.0.push(ReadinessFinding {
+            check,
+            severity,
+            message: message.into(),
+            fix,
+        });
+    }
+
+    fn ok(&mut self, check: ReadinessCheck, message: impl Into<String>) {
+        self.add(check, Severity::Ok, message, None);
+    }
+}
+
+/// Runs every launch check for the profile, instead of stopping at the first problem like `check_game_launch`.
+pub async fn get_readiness_report(id: u32, state: AppState<'_>) -> anyhow::Result<ReadinessReport> {
+    // The server is asked without holding the lock, like in `check_game_launch`
+    let (profile, mut cache, max_age) = {
+        let state = state.read().await;
+        let profile = state
+            .profiles
+            .map
+            .get(&id)
+            .cloned()
+            .ok_or(anyhow!("No profile with the given ID found."))?;
+
+        let max_age = state.config.get_manifest_cache_ttl();
+        (profile, state.manifest_cache.clone(), max_age)
+    };
+
+    let report = check_profile(&profile, &mut cache, max_age).await;
+
+    let mut state = state.write().await;
+    state.manifest_cache.merge(cache);
+    if let Err(err) = state.manifest_cache.save(&state.local_data_dir) {
+        tracing::warn!("Could not save manifest cache: {err:?}");
+    }
+
+    Ok(report)
+}
+
+async fn check_profile(
+    profile: &Profile,
+    cache: &mut ManifestCache,
+    max_age: Duration,
+) -> ReadinessReport {
+    let mut findings = Findings(vec![]);
+
+    check_game_directory(profile, &mut findings);
+    check_frontend(profile, &mut findings);
+
+    if !profile.is_retail && profile.server.is_some() {
+        check_bootloader(profile, &mut findings);
+        check_server_files(profile, cache, max_age, &mut findings).await;
+    }
+
+    check_pivot(profile, &mut findings);
+    check_credentials(profile, &mut findings);
+
+    if profile.use_windower {
+        check_windower_settings(profile, &mut findings);
+    } else {
+        check_ashita_files(profile, &mut findings);
+    }
+
+    let findings = findings.0;
+    ReadinessReport {
+        ready: !findings
+            .iter()
+            .any(|finding| finding.severity == Severity::Error),
+        findings,
+    }
+}
+
+fn check_game_directory(profile: &Profile, findings: &mut Findings) {
+    let check = ReadinessCheck::GameDirectory;
+
+    let Some(game_directory) = &profile.install.directory else {
+        findings.add(
+            check,
+            Severity::Error,
+            "No game directory is set.",
+            Some(FixAction::SetGameDirectory),
+        );
+        return;
+    };
+
+    if game_directory.join("FINAL FANTASY XI").exists() {
+        findings.ok(check, "The game is installed.");
+    } else {
+        findings.add(
+            check,
+            Severity::Error,
+            format!("The game is not installed in {}.", game_directory.display()),
+            Some(FixAction::InstallGame),
+        );
+    }
+}
+
+fn check_frontend(profile: &Profile, findings: &mut Findings) {
+    let check = ReadinessCheck::Frontend;
+
+    let (name, directory, exe, fix) = if profile.use_windower {
+        (
+            "Windower",
+            profile.install.get_windower_dir(),
+            "Windower.exe",
+            FixAction::SetWindowerDirectory,
+        )
+    } else {
+        (
+            "Ashita",
+            profile.install.get_ashita_dir(),
+            "Ashita-cli.exe",
+            FixAction::SetAshitaDirectory,
+        )
+    };
+
+    let Some(directory) = directory else {
+        findings.add(
+            check,
+            Severity::Error,
+            format!("No {name} directory is set."),
+            Some(fix),
+        );
+        return;
+    };
+
+    if !directory.join(exe).exists() {
+        findings.add(
+            check,
+            Severity::Error,
+            format!("Could not find {exe} in {}.", directory.display()),
+            Some(fix),
+        );
+        return;
+    }
+
+    findings.ok(check, format!("{name} is installed."));
+
+    if let (false, Some(required)) = (profile.use_windower, &profile.required_ashita_version) {
+        let installed = detect_ashita_version(&directory);
+        match check_ashita_version(required, installed.as_deref()) {
+            Some(reason) => findings.add(check, Severity::Warning, reason, Some(fix)),
+            None => findings.ok(check, "The Ashita version matches the server."),
+        }
+    }
+}
+
+fn check_bootloader(profile: &Profile, findings: &mut Findings) {
+    let check = ReadinessCheck::Bootloader;
+
+    match profile
+        .get_bootloader_path()
+        .map(|path| path.join("xiloader.exe"))
+    {
+        Some(path) if path.exists() => findings.ok(check, "The bootloader is installed."),
+        Some(path) => findings.add(
+            check,
+            Severity::Error,
+            format!("Could not find the bootloader at {}.", path.display()),
+            Some(FixAction::UpdateServerFiles),
+        ),
+        None => findings.add(
+            check,
+            Severity::Error,
+            "Could not determine the bootloader location.",
+            None,
+        ),
+    }
+}
+
+async fn check_server_files(
+    profile: &Profile,
+    cache: &mut ManifestCache,
+    max_age: Duration,
+    findings: &mut Findings,
+) {
+    let check = ReadinessCheck::ServerFiles;
+
+    let versions_info = match get_versions_info(profile, cache, max_age).await {
+        Ok(Some(versions_info)) => versions_info,
+        Ok(None) => {
+            findings.add(
+                check,
+                Severity::Info,
+                "The server does not provide any files.",
+                None,
+            );
+            return;
+        }
+        Err(err @ ServerRequestError::Verification(_)) => {
+            findings.add(check, Severity::Error, err.to_string(), None);
+            return;
+        }
+        Err(err) => {
+            // Launching is refused in this case too, see `LaunchStatus::launch_blocker`
+            let severity = if profile.offline_policy == OfflinePolicy::Block {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            findings.add(
+                check,
+                severity,
+                format!("Could not check for server file updates. {err}"),
+                None,
+            );
+            return;
+        }
+    };
+
+    if let Some(required) = &versions_info.client {
+        let installed = profile
+            .install
+            .directory
+            .as_ref()
+            .and_then(|directory| detect_client_version(directory));
+
+        match required.check(installed.as_ref()) {
+            Some(mismatch) => findings.add(
+                ReadinessCheck::ClientVersion,
+                if mismatch.blocking {
+                    Severity::Error
+                } else {
+                    Severity::Warning
+                },
+                mismatch.reason,
+                Some(FixAction::UpdateClient),
+            ),
+            None => findings.ok(
+                ReadinessCheck::ClientVersion,
+                "The client version matches the server.",
+            ),
+        }
+    }
+
+    for component in versions_info.all_components() {
+        let version_path = match component.get_checked_version_path(profile) {
+            Ok(version_path) => version_path,
+            Err(err) => {
+                findings.add(check, Severity::Error, format!("{err:#}"), None);
+                continue;
+            }
+        };
+
+        match get_component_change(profile, &component, &version_path).await {
+            VersionChange::Unchanged => {
+                findings.ok(check, format!("{} is up to date.", component.name))
+            }
+            VersionChange::Upgrade => findings.add(
+                check,
+                Severity::Error,
+                format!(
+                    "{} needs to be updated to version {}.",
+                    component.name, component.version
+                ),
+                Some(FixAction::UpdateServerFiles),
+            ),
+            VersionChange::Downgrade => findings.add(
+                check,
+                Severity::Warning,
+                format!(
+                    "The server has an older version {} of {}.",
+                    component.version, component.name
+                ),
+                Some(FixAction::UpdateServerFiles),
+            ),
+        }
+    }
+}
+
+fn check_pivot(profile: &Profile, findings: &mut Findings) {
+    let check = ReadinessCheck::PivotPlugin;
+
+    let pivot_path = if profile.use_windower {
+        profile
+            .install
+            .get_windower_dir()
+            .map(|dir| dir.join("addons/XIPivot"))
+    } else {
+        profile
+            .install
+            .get_ashita_dir()
+            .map(|dir| dir.join("polplugins/pivot.dll"))
+    };
+
+    let Some(pivot_path) = pivot_path else {
+        return;
+    };
+
+    if pivot_path.exists() {
+        findings.ok(check, "The pivot plugin is installed.");
+        return;
+    }
+
+    // Without any DATs to overlay, a missing pivot plugin doesn't matter
+    let uses_pivot = !profile.extra_pivots.is_empty()
+        || profile
+            .get_pivot_dat_path()
+            .map(|path| path.exists())
+            .unwrap_or_default();
+
+    findings.add(
+        check,
+        if uses_pivot {
+            Severity::Error
+        } else {
+            Severity::Warning
+        },
+        format!(
+            "Could not find the pivot plugin at {}.",
+            pivot_path.display()
+        ),
+        Some(FixAction::InstallPivot),
+    );
+}
+
+fn check_credentials(profile: &Profile, findings: &mut Findings) {
+    let check = ReadinessCheck::Credentials;
+
+    if profile.is_retail || profile.manual_auth {
+        findings.ok(check, "Credentials are entered in game.");
+        return;
+    }
+
+    if profile.account_name.is_none() {
+        findings.add(
+            check,
+            Severity::Error,
+            "No account name is set.",
+            Some(FixAction::SetAccountName),
+        );
+    }
+
+    if needs_password(profile) {
+        findings.add(
+            check,
+            if profile.auth_kind == AuthKind::Password {
+                Severity::Warning
+            } else {
+                Severity::Info
+            },
+            "A password will be requested on launch.",
+            Some(FixAction::EnterPassword),
+        );
+    } else if profile.account_name.is_some() {
+        findings.ok(check, "Credentials are set.");
+    }
+}
+
+fn check_ashita_files(profile: &Profile, findings: &mut Findings) {
+    let Some(ashita_dir) = profile.install.get_ashita_dir() else {
+        return;
+    };
+
+    let profile_filename = profile.get_profile_filename();
+
+    let script_path = ashita_dir.join(format!("scripts/{profile_filename}.txt"));
+    if !script_path.exists() {
+        findings.add(
+            ReadinessCheck::ScriptFile,
+            Severity::Warning,
+            format!(
+                "The script file {} has not been created yet.",
+                script_path.display()
+            ),
+            Some(FixAction::SaveProfile),
+        );
+    } else if std::fs::read_to_string(&script_path)
+        .map(|content| ashita::has_launcher_section(&content))
+        .unwrap_or_default()
+    {
+        findings.ok(ReadinessCheck::ScriptFile, "The script file is set up.");
+    } else {
+        findings.add(
+            ReadinessCheck::ScriptFile,
+            Severity::Warning,
+            format!(
+                "The script file {} is not managed by the launcher, and will be replaced.",
+                script_path.display()
+            ),
+            Some(FixAction::SaveProfile),
+        );
+    }
+
+    let ini_path = ashita_dir.join(format!("config/boot/{profile_filename}.ini"));
+    check_writeable(&ini_path, findings);
+}
+
+fn check_windower_settings(profile: &Profile, findings: &mut Findings) {
+    let Some(windower_dir) = profile.install.get_windower_dir() else {
+        return;
+    };
+
+    if profile.windower_profile.is_none() {
+        findings.add(
+            ReadinessCheck::ConfigWriteable,
+            Severity::Error,
+            "No Windower profile is set.",
+            Some(FixAction::SetWindowerProfile),
+        );
+    }
+
+    check_writeable(&windower_dir.join("settings.xml"), findings);
+}
+
+fn check_writeable(path: &PathBuf, findings: &mut Findings) {
+    let check = ReadinessCheck::ConfigWriteable;
+
+    if is_writeable(path) {
+        findings.ok(check, format!("{} is writeable.", path.display()));
+    } else {
+        findings.add(
+            check,
+            Severity::Error,
+            format!("{} can not be written to.", path.display()),
+            Some(FixAction::CheckPermissions),
+        );
+    }
+}
+
+/// Checks if the file can be written, or created if it doesn't exist, without modifying anything.
+fn is_writeable(path: &Path) -> bool {
+    if path.exists() {
+        return OpenOptions::new().append(true).open(path).is_ok();
+    }
+
+    // Check the closest existing parent directory instead
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current.exists() {
+            return current
+                .metadata()
+                .map(|metadata| !metadata.permissions().readonly())
+                .unwrap_or_default();
+        }
+        dir = current.parent();
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::profiles::InstallConfig;
+
+    fn worst(report: &ReadinessReport, check: ReadinessCheck) -> Option<Severity> {
+        report
+            .findings
+            .iter()
+            .filter(|finding| finding.check == check)
+            .map(|finding| finding.severity)
+            .max()
+    }
+
+    #[tokio::test]
+    async fn reports_every_problem() {
+        let profile = Profile {
+            auth_kind: AuthKind::Password,
+            ..Default::default()
+        };
+
+        let report = check_profile(
+            &profile,
+            &mut ManifestCache::default(),
+            Duration::from_secs(60),
+        )
+        .await;
+
+        assert!(!report.ready);
+        assert_eq!(
+            worst(&report, ReadinessCheck::GameDirectory),
+            Some(Severity::Error)
+        );
+        assert_eq!(
+            worst(&report, ReadinessCheck::Frontend),
+            Some(Severity::Error)
+        );
+        assert_eq!(
+            worst(&report, ReadinessCheck::Credentials),
+            Some(Severity::Error)
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_update_checks_follow_the_offline_policy() {
+        let dir = std::env::temp_dir().join("xi-launcher-readiness-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("FINAL FANTASY XI")).unwrap();
+        std::fs::create_dir_all(dir.join("Ashita")).unwrap();
+
+        let mut profile = Profile {
+            server: Some("example".to_string()),
+            // Nothing listens on port 1, so the update check fails right away
+            server_info_addr: Some("http://127.0.0.1:1".to_string()),
+            install: InstallConfig {
+                directory: Some(dir.clone()),
+                ..Default::default()
+            },
+            offline_policy: OfflinePolicy::LaunchAnyway,
+            ..Default::default()
+        };
+
+        let mut cache = ManifestCache::default();
+        let report = check_profile(&profile, &mut cache, Duration::ZERO).await;
+        assert_eq!(
+            worst(&report, ReadinessCheck::ServerFiles),
+            Some(Severity::Warning)
+        );
+
+        profile.offline_policy = OfflinePolicy::Block;
+        let report = check_profile(&profile, &mut cache, Duration::ZERO).await;
+        assert_eq!(
+            worst(&report, ReadinessCheck::ServerFiles),
+            Some(Severity::Error)
+        );
+        assert!(!report.ready);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn warns_about_an_older_ashita() {
+        let dir = std::env::temp_dir().join("xi-launcher-readiness-ashita-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Ashita-cli.exe"), b"MZ").unwrap();
+
+        let mut profile = Profile {
+            install: InstallConfig {
+                ashita_directory: Some(dir.clone()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut findings = Findings(vec![]);
+        check_frontend(&profile, &mut findings);
+        assert!(findings
+            .0
+            .iter()
+            .all(|finding| finding.severity == Severity::Ok));
+
+        // The version of the fake executable can't be detected
+        profile.required_ashita_version = Some("4.1".to_string());
+        let mut findings = Findings(vec![]);
+        check_frontend(&profile, &mut findings);
+        assert!(findings
+            .0
+            .iter()
+            .any(|finding| finding.severity == Severity::Warning));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::{config::profiles::Profile, http, util::load_json_or_default};
+
+/// Response of a server-list URL.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct ServerDirectory {
+    #[serde(default)]
+    pub servers: Vec<ServerDirectoryEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ServerDirectoryEntry {
+    pub name: String,
+
+    #[serde(default)]
+    pub description: Option<String>,
+
+    pub hostname: String,
+
+    #[serde(default)]
+    pub info_addr: Option<String>,
+
+    #[serde(default)]
+    pub website: Option<String>,
+
+    /// Ashita version the server requires at least. Profiles created for the server use Ashita when set,
+    /// and warn when the installed Ashita is older.
+    #[serde(default)]
+    pub ashita_version: Option<String>,
+
+    #[serde(default)]
+    pub recommended_pivots: Vec<String>,
+
+    /// The server-list URL this entry came from. Filled in by the launcher.
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct ServerDirectoryFeed {
+    pub url: String,
+    pub servers: Vec<ServerDirectoryEntry>,
+
+    /// Unix timestamp of when the feed was last fetched successfully.
+    #[serde(default)]
+    pub fetched_at: Option<u64>,
+
+    /// Set when the latest fetch failed, and the servers come from the local cache.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ServerDirectoryCache {
+    #[serde(default)]
+    feeds: BTreeMap<String, ServerDirectoryFeed>,
+}
+
+pub const SERVER_DIRECTORY_CACHE_FILENAME: &'static str = "server_directory.json";
+
+impl ServerDirectoryCache {
+    fn get_path(dir: &PathBuf) -> PathBuf {
+        dir.join(SERVER_DIRECTORY_CACHE_FILENAME)
+    }
+}
+
+/// Fetches every server-list URL, falling back to the locally cached copy of a feed when it can't be reached.
+pub async fn get_server_directory(
+    local_data_dir: &PathBuf,
+    urls: &[String],
+) -> anyhow::Result<Vec<ServerDirectoryFeed>> {
+    let cache_path = ServerDirectoryCache::get_path(local_data_dir);
+    let mut cache: ServerDirectoryCache = load_json_or_default(&cache_path);
+
+    let mut feeds = vec![];
+    for url in urls {
+        match fetch_feed(url).await {
+            Ok(servers) => {
+                let feed = ServerDirectoryFeed {
+                    url: url.clone(),
+                    servers,
+                    fetched_at: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .ok()
+                        .map(|duration| duration.as_secs()),
+                    error: None,
+                };
+                cache.feeds.insert(url.clone(), feed.clone());
+                feeds.push(feed);
+            }
+            Err(err) => {
+                tracing::warn!("Could not fetch server list from {url}: {err:?}");
+                let mut feed =
+                    cache
+                        .feeds
+                        .get(url)
+                        .cloned()
+                        .unwrap_or_else(|| ServerDirectoryFeed {
+                            url: url.clone(),
+                            ..Default::default()
+                        });
+                feed.error = Some(format!("{err:#}"));
+                feeds.push(feed);
+            }
+        }
+    }
+
+    // Forget feeds that are no longer configured
+    cache.feeds.retain(|url, _| urls.contains(url));
+
+    let file = File::create(cache_path)?;
+    serde_json::to_writer(file, &cache)?;
+
+    Ok(feeds)
+}
+
+async fn fetch_feed(url: &str) -> anyhow::Result<Vec<ServerDirectoryEntry>> {
+    let directory: ServerDirectory = http::client()
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(directory
+        .servers
+        .into_iter()
+        .map(|mut entry| {
+            entry.source = Some(url.to_string());
+            entry
+        })
+        .collect())
+}
+
+impl ServerDirectoryEntry {
+    /// Creates a new profile connecting to the server, with other settings taken from the template profile.
+    /// Settings that only apply to the template's server are not carried over, and servers that
+    /// require Ashita get a profile that uses it.
+    pub fn to_profile(&self, template: Profile) -> Profile {
+        let mut extra_pivots = template.extra_pivots;
+        for pivot in &self.recommended_pivots {
+            if !extra_pivots.contains(pivot) {
+                extra_pivots.push(pivot.clone());
+            }
+        }
+
+        Profile {
+            name: Some(self.name.clone()),
+            server: Some(self.hostname.clone()),
+            server_nickname: Some(self.name.clone()),
+            server_info_addr: self.info_addr.clone(),
+            server_public_key: None,
+            server_ca_certificate: None,
+            required_ashita_version: self.ashita_version.clone(),
+            is_retail: false,
+            use_windower: template.use_windower && self.ashita_version.is_none(),
+            account_name: None,
+            password: None,
+            hairpin: false,
+            extra_pivots,
+            windower_profile: None,
+            offline_policy: Default::default(),
+            pinned_versions: Default::default(),
+            declined_downgrades: Default::default(),
+            ..template
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_keep_nothing_server_specific_from_the_template() {
+        let entry = ServerDirectoryEntry {
+            name: "Example".to_string(),
+            description: None,
+            hostname: "play.example.com".to_string(),
+            info_addr: Some("https://play.example.com/info".to_string()),
+            website: None,
+            ashita_version: Some("4.1".to_string()),
+            recommended_pivots: vec!["example".to_string()],
+            source: None,
+        };
+        let template = Profile {
+            server: Some("other.example.com".to_string()),
+            server_public_key: Some("RWQ".to_string()),
+            server_ca_certificate: Some(PathBuf::from("other.pem")),
+            use_windower: true,
+            hairpin: true,
+            windower_profile: Some("Other".to_string()),
+            pinned_versions: BTreeMap::from([("xiloader".to_string(), "1.0.0".to_string())]),
+            declined_downgrades: BTreeMap::from([("dats".to_string(), "2".to_string())]),
+            extra_pivots: vec!["hd".to_string()],
+            ..Default::default()
+        };
+
+        let profile = entry.to_profile(template);
+        assert_eq!(profile.server.as_deref(), Some("play.example.com"));
+        assert_eq!(profile.server_public_key, None);
+        assert_eq!(profile.server_ca_certificate, None);
+        assert!(!profile.use_windower);
+        assert_eq!(profile.required_ashita_version.as_deref(), Some("4.1"));
+        assert!(!profile.hairpin);
+        assert_eq!(profile.windower_profile, None);
+        assert!(profile.pinned_versions.is_empty());
+        assert!(profile.declined_downgrades.is_empty());
+        assert_eq!(profile.extra_pivots, vec!["hd", "example"]);
+    }
+}
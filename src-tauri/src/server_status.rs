@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::ipc::Channel;
+use tokio::{net::TcpStream, time::timeout};
+use tokio_util::sync::CancellationToken;
+
+use crate::{config::profiles::Profile, http};
+
+/// Port that xiloader authenticates against.
+pub const LOGIN_PORT: u16 = 54231;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ServerStatus {
+    pub profile_id: u32,
+    pub online: bool,
+
+    /// Whether the server info endpoints are only offered over plain HTTP.
+    pub plain_http: bool,
+
+    pub login: ProbeResult,
+    pub info: ProbeResult,
+    pub status: Option<ServerStatusInfo>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Type)]
+pub struct ProbeResult {
+    pub online: bool,
+    pub latency_ms: Option<u32>,
+    pub error: Option<String>,
+}
+
+/// Optional information a server can publish at `/status` on its info address.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ServerStatusInfo {
+    #[serde(default)]
+    pub players: Option<u32>,
+
+    #[serde(default)]
+    pub motd: Option<String>,
+}
+
+/// Probes the servers of the given profiles until cancelled, or the channel is closed.
+pub async fn refresh_server_statuses(
+    profiles: Vec<Profile>,
+    channel: Channel<ServerStatus>,
+    token: CancellationToken,
+) {
+    loop {
+        let probes = profiles
+            .iter()
+            .filter(|profile| !profile.is_retail && profile.server.is_some())
+            .map(|profile| {
+                let channel = channel.clone();
+                async move { channel.send(probe_server(profile).await).is_ok() }
+            });
+
+        tokio::select! {
+            () = token.cancelled() => {
+                return;
+            }
+            results = futures_util::future::join_all(probes) => {
+                if results.iter().any(|sent| !sent) {
+                    tracing::debug!("Server status channel closed.");
+                    return;
+                }
+            }
+        }
+
+        tokio::select! {
+            () = token.cancelled() => {
+                return;
+            }
+            () = tokio::time::sleep(REFRESH_INTERVAL) => {}
+        }
+    }
+}
+
+pub async fn probe_server(profile: &Profile) -> ServerStatus {
+    let server = profile.server.clone().unwrap_or_default();
+
+    let info_url = profile.get_server_info_url();
+    let info_socket_addr = info_url.as_ref().ok().and_then(|url| {
+        Some(format!(
+            "{}:{}",
+            url.host_str()?,
+            url.port_or_known_default()?
+        ))
+    });
+
+    let (login, info) = tokio::join!(probe_tcp(&format!("{server}:{LOGIN_PORT}")), async {
+        match (&info_url, &info_socket_addr) {
+            (Ok(_), Some(addr)) => probe_tcp(addr).await,
+            (Err(err), _) => ProbeResult {
+                error: Some(format!("{err:#}")),
+                ..Default::default()
+            },
+            (Ok(url), None) => ProbeResult {
+                error: Some(format!("Could not determine host and port of {url}")),
+                ..Default::default()
+            },
+        }
+    });
+
+    let status = if info.online {
+        get_status_info(profile).await
+    } else {
+        None
+    };
+
+    ServerStatus {
+        profile_id: profile.id,
+        online: login.online,
+        plain_http: info_url
+            .map(|url| url.scheme() == "http")
+            .unwrap_or_default(),
+        login,
+        info,
+        status,
+    }
+}
+
+async fn probe_tcp(addr: &str) -> ProbeResult {
+    let start = Instant::now();
+    match timeout(PROBE_TIMEOUT, TcpStream::connect(addr)).await {
+        Ok(Ok(_stream)) => ProbeResult {
+            online: true,
+            latency_ms: Some(start.elapsed().as_millis() as u32),
+            error: None,
+        },
+        Ok(Err(err)) => ProbeResult {
+            error: Some(err.to_string()),
+            ..Default::default()
+        },
+        Err(_) => ProbeResult {
+            error: Some(format!(
+                "Timed out after {} seconds",
+                PROBE_TIMEOUT.as_secs()
+            )),
+            ..Default::default()
+        },
+    }
+}
+
+async fn get_status_info(profile: &Profile) -> Option<ServerStatusInfo> {
+    http::server_client(profile)
+        .ok()?
+        .get(profile.get_server_info_endpoint("status").ok()?)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()
+}
@@ -0,0 +1,88 @@
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::{anyhow, Context};
+use minisign_verify::{PublicKey, Signature};
+
+use crate::config::profiles::Profile;
+
+/// Parses the public key pinned for the profile's server, if any.
+/// Accepts either the base64 key line or the full contents of a `minisign.pub` file.
+pub fn get_server_key(profile: &Profile) -> anyhow::Result<Option<PublicKey>> {
+    let Some(key) = profile
+        .server_public_key
+        .as_ref()
+        .map(|key| key.trim())
+        .filter(|key| !key.is_empty())
+    else {
+        return Ok(None);
+    };
+
+    let public_key = if key.lines().count() > 1 {
+        PublicKey::decode(key)
+    } else {
+        PublicKey::from_base64(key)
+    }
+    .map_err(|err| anyhow!("Invalid server public key: {err}"))?;
+
+    Ok(Some(public_key))
+}
+
+pub fn verify_bytes(key: &PublicKey, data: &[u8], signature: &str) -> anyhow::Result<()> {
+    let signature =
+        Signature::decode(signature).map_err(|err| anyhow!("Invalid signature: {err}"))?;
+
+    key.verify(data, &signature, false)
+        .map_err(|err| anyhow!("Signature verification failed: {err}"))
+}
+
+pub fn verify_file(key: &PublicKey, path: &Path, signature: &str) -> anyhow::Result<()> {
+    let signature =
+        Signature::decode(signature).map_err(|err| anyhow!("Invalid signature: {err}"))?;
+
+    let mut verifier = key
+        .verify_stream(&signature)
+        .map_err(|err| anyhow!("Unsupported signature for {}: {err}", path.display()))?;
+
+    let mut file =
+        File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        verifier.update(&buffer[..read]);
+    }
+
+    verifier.finalize().map_err(|err| {
+        anyhow!(
+            "Signature verification failed for {}: {err}",
+            path.display()
+        )
+    })
+}
+
+/// Verifies a downloaded file against its signature from the manifest, when the server has a pinned key.
+/// Files can be several gigabytes, so they are hashed on a blocking thread.
+pub async fn verify_artifact(
+    key: Option<&PublicKey>,
+    path: &Path,
+    signature: Option<&String>,
+) -> anyhow::Result<()> {
+    let Some(key) = key else {
+        return Ok(());
+    };
+
+    let signature = signature.ok_or_else(|| {
+        anyhow!(
+            "Missing signature for {}, which is required by the pinned server key.",
+            path.display()
+        )
+    })?;
+
+    let key = key.clone();
+    let path = path.to_path_buf();
+    let signature = signature.clone();
+    tokio::task::spawn_blocking(move || verify_file(&key, &path, &signature)).await?
+}
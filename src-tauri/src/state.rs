@@ -1,11 +1,14 @@
-use std::{collections::HashMap, fs::File, path::PathBuf};
+use std::{collections::HashMap, fs::File, path::PathBuf, sync::Arc};
 
 use tauri::{async_runtime::RwLock, App, Manager, State};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    check_game::VersionsInfo,
+    bandwidth,
     config::{launcher::LauncherConfig, profiles::Profiles},
+    download_cache::DownloadCache,
+    http,
+    manifest_cache::ManifestCache,
     util::load_json_or_default,
 };
 
@@ -15,24 +18,44 @@ pub struct AppStateData {
     pub local_data_dir: PathBuf,
     pub config: LauncherConfig,
     pub profiles: Profiles,
-    pub update_cache: HashMap<String, VersionsInfo>,
+    pub manifest_cache: ManifestCache,
+    pub download_cache: Arc<DownloadCache>,
     pub ongoing_tasks: HashMap<u32, CancellationToken>,
+    pub server_status_task: Option<CancellationToken>,
+    /// Result of the last launch check of each profile: why it must not be launched, or `None` if it can be.
+    /// Cleared whenever the profile or its files change, so the next launch checks again.
+    pub launch_blockers: HashMap<u32, Option<String>>,
 }
 
 impl AppStateData {
     pub async fn new(app: &App) -> anyhow::Result<Self> {
         let local_data_dir = app.path().app_local_data_dir().unwrap();
 
-        let config = load_json_or_default(&LauncherConfig::get_path(&local_data_dir));
+        let config: LauncherConfig =
+            load_json_or_default(&LauncherConfig::get_path(&local_data_dir));
+        bandwidth::limiter().configure(&config.bandwidth);
+        if let Err(err) = http::configure(&config.http) {
+            tracing::error!("Invalid HTTP settings, using defaults: {err:#}");
+        }
 
         let profiles_config = load_json_or_default(&Profiles::get_path(&local_data_dir));
 
+        let manifest_cache = load_json_or_default(&ManifestCache::get_path(&local_data_dir));
+
+        let download_cache = Arc::new(DownloadCache::new(
+            &local_data_dir,
+            config.get_download_cache_limit(),
+        ));
+
         Ok(Self {
             local_data_dir,
             config,
             profiles: profiles_config,
-            update_cache: Default::default(),
+            manifest_cache,
+            download_cache,
             ongoing_tasks: Default::default(),
+            server_status_task: None,
+            launch_blockers: Default::default(),
         })
     }
 
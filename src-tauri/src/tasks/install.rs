@@ -1,11 +1,16 @@
 use std::{
-    path::PathBuf,
-    process::Command,
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
     time::{Duration, Instant},
     u64,
 };
 
 use anyhow::{anyhow, Context};
+use minisign_verify::PublicKey;
+use reqwest::Client;
 use serde::Serialize;
 use specta::Type;
 use tauri::ipc::Channel;
@@ -13,20 +18,67 @@ use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    check_game::DownloadInfo,
-    file_download::{download_files, DownloadMessage},
+    check_game::{DownloadInfo, FileInstallConfig},
+    download_cache::DownloadCache,
+    extract,
+    file_download::{
+        download_files, find_download_path, get_download_path, record_download_path, verify_file,
+        AttemptFailure, DownloadFile, DownloadMessage, FileProgress, PROGRESS_INTERVAL,
+    },
+    filename, http, signature,
     state::AppState,
 };
 
-pub struct InstallTask;
+pub struct InstallTask {
+    pub download_info: DownloadInfo,
+    pub download_dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub server_key: Option<PublicKey>,
+    /// Client trusting the server's CA certificate, since installers are often hosted by the server.
+    pub client: Client,
+    pub max_concurrent: usize,
+    pub cache: Arc<DownloadCache>,
+    /// Installer parts or an installed client on this computer to install from instead of downloading.
+    pub local_source: Option<PathBuf>,
+}
+
+/// Files on this computer that the game can be installed from without downloading it.
+#[derive(Debug)]
+enum LocalSource {
+    /// An installed client, copied from the first path into the second.
+    Client(PathBuf, PathBuf),
+    /// Installer archive parts in extraction order, with the manifest entry they match, if any.
+    Installer(Vec<(PathBuf, Option<FileInstallConfig>)>),
+}
 
 #[derive(Debug, Clone, Serialize, Type)]
 #[serde(tag = "event", content = "data")]
 pub enum InstallTaskProgress {
     Pending,
-    DownloadStarted { content_length: u64 },
-    DownloadProgress { finished_length: u64 },
+    DownloadStarted {
+        content_length: u64,
+    },
+    DownloadProgress {
+        finished_length: u64,
+        content_length: u64,
+        bytes_per_second: u64,
+        eta_secs: Option<u64>,
+    },
+    /// Progress of a single file of the download.
+    FileProgress(FileProgress),
+    /// A download request failed, and is retried or continued at a mirror.
+    DownloadAttemptFailed(AttemptFailure),
     Installing,
+    /// Progress of extracting the installer, in uncompressed bytes.
+    Extracting {
+        finished_length: u64,
+        total_length: u64,
+    },
+    /// Progress of copying an installed client.
+    Copying {
+        finished_length: u64,
+        total_length: u64,
+    },
     Complete,
     Error(String),
 }
@@ -34,6 +86,7 @@ pub enum InstallTaskProgress {
 pub async fn install_client(
     id: u32,
     download_info: DownloadInfo,
+    local_source: Option<PathBuf>,
     state: AppState<'_>,
     channel: Channel<InstallTaskProgress>,
 ) -> anyhow::Result<()> {
@@ -51,6 +104,11 @@ pub async fn install_client(
         .as_ref()
         .cloned()
         .ok_or_else(|| anyhow!("Expected game directory."))?;
+
+    let server_key = signature::get_server_key(profile)?;
+    let client = http::server_client(profile)?;
+    let max_concurrent = read_state.config.get_max_concurrent_downloads();
+    let cache = read_state.download_cache.clone();
     drop(read_state);
 
     let download_dir = install_dir.join("downloads");
@@ -58,7 +116,17 @@ pub async fn install_client(
     let token = CancellationToken::new();
     state.write().await.ongoing_tasks.insert(id, token.clone());
 
-    let mut receiver = InstallTask::start(download_info, download_dir, install_dir, token.clone());
+    let mut receiver = InstallTask {
+        download_info,
+        download_dir,
+        output_dir: install_dir,
+        server_key,
+        client,
+        max_concurrent,
+        cache,
+        local_source,
+    }
+    .start(token.clone());
 
     // Return true if it break out of receive loop
     let handle_message = async move |message: Option<InstallTaskProgress>| -> anyhow::Result<bool> {
@@ -92,23 +160,19 @@ pub async fn install_client(
         }
     }
 
-    state.write().await.ongoing_tasks.remove(&id);
+    // The installed files changed, so the next launch checks them again
+    let mut state = state.write().await;
+    state.ongoing_tasks.remove(&id);
+    state.launch_blockers.remove(&id);
 
     Ok(())
 }
 
 impl InstallTask {
-    pub fn start(
-        download_info: DownloadInfo,
-        download_dir: PathBuf,
-        output_dir: PathBuf,
-        token: CancellationToken,
-    ) -> mpsc::Receiver<InstallTaskProgress> {
+    pub fn start(self, token: CancellationToken) -> mpsc::Receiver<InstallTaskProgress> {
         let (tx, rx) = mpsc::channel(128);
         tokio::spawn(async move {
-            if let Err(err) =
-                Self::execute(download_info, download_dir, output_dir, tx.clone(), token).await
-            {
+            if let Err(err) = self.execute(tx.clone(), token).await {
                 tracing::error!("Error during install: {err:?}");
                 let _ = tx.send(InstallTaskProgress::Error(err.to_string())).await;
             }
@@ -117,44 +181,160 @@ impl InstallTask {
     }
 
     async fn execute(
-        download_info: DownloadInfo,
-        download_dir: PathBuf,
-        output_dir: PathBuf,
+        self,
         sender: mpsc::Sender<InstallTaskProgress>,
         token: CancellationToken,
     ) -> anyhow::Result<()> {
-        if output_dir.join("FINAL FANTASY XI").exists() {
+        if self.output_dir.join("FINAL FANTASY XI").exists() {
             sender.send(InstallTaskProgress::Complete).await?;
             return Ok(());
         }
 
-        Self::download_step(download_info, download_dir, output_dir, sender, token).await
+        match self.local_source.clone() {
+            Some(source) => self.local_step(source, sender, token).await,
+            None => self.download_step(sender, token).await,
+        }
     }
 
-    async fn download_step(
-        download_info: DownloadInfo,
-        download_dir: PathBuf,
-        output_dir: PathBuf,
+    async fn local_step(
+        self,
+        source: PathBuf,
         sender: mpsc::Sender<InstallTaskProgress>,
         token: CancellationToken,
     ) -> anyhow::Result<()> {
-        if !download_info.iter().any(|file| file.url.ends_with(".exe")) {
-            return Err(anyhow!(
-                "Expected to find an executable installer in the download file URLs, but it was not present."
-            ));
+        sender.send(InstallTaskProgress::Pending).await?;
+
+        let local_source = {
+            let output_dir = self.output_dir.clone();
+            let download_info = self.download_info.clone();
+            tokio::task::spawn_blocking(move || {
+                LocalSource::inspect(&source, &output_dir, &download_info)
+            })
+            .await??
+        };
+
+        match local_source {
+            LocalSource::Client(from, to) => {
+                tokio::task::spawn_blocking(move || {
+                    Self::copy_step(self.output_dir, sender, &from, &to, token)
+                })
+                .await?
+            }
+            LocalSource::Installer(files) => {
+                // Local files are verified like downloads, when the manifest describes them
+                for (path, file) in &files {
+                    let Some(file) = file else {
+                        continue;
+                    };
+
+                    let download_file = DownloadFile {
+                        url: file.url.clone(),
+                        mirrors: vec![],
+                        size: file.size,
+                        sha256: file.sha256.clone(),
+                    };
+                    let verified = match verify_file(&download_file, path).await {
+                        Ok(()) => {
+                            signature::verify_artifact(
+                                self.server_key.as_ref(),
+                                path,
+                                file.signature.as_ref(),
+                            )
+                            .await
+                        }
+                        Err(err) => Err(err),
+                    };
+                    if let Err(err) = verified {
+                        return Err(anyhow!(
+                            "Failed verifying local installer {}: {err:#}",
+                            path.display()
+                        ));
+                    }
+                }
+
+                let parts: Vec<PathBuf> = files.into_iter().map(|(path, _)| path).collect();
+                tokio::task::spawn_blocking(move || {
+                    Self::install_step(self.output_dir, sender, &parts, token)
+                })
+                .await?
+            }
         }
+    }
+
+    async fn download_step(
+        self,
+        sender: mpsc::Sender<InstallTaskProgress>,
+        token: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let InstallTask {
+            download_info,
+            download_dir,
+            output_dir,
+            server_key,
+            client,
+            max_concurrent,
+            cache,
+            local_source: _,
+        } = self;
+
+        let installer_urls = installer_parts(&download_info)?;
 
         sender.send(InstallTaskProgress::Pending).await?;
 
+        let manifest: HashMap<String, FileInstallConfig> = download_info
+            .iter()
+            .map(|file| (file.url.clone(), file.clone()))
+            .collect();
+
+        // Files another profile already downloaded are verified like any earlier download
+        for file in &download_info {
+            let downloaded = find_download_path(&download_dir, &file.url).await;
+            if downloaded.is_some_and(|path| path.exists()) {
+                continue;
+            }
+
+            // Never replace a file that another URL ending in the same name was saved as
+            let target = get_download_path(&download_dir, &file.url);
+            if target.exists() {
+                continue;
+            }
+
+            let cache = cache.clone();
+            let url = file.url.clone();
+            let sha256 = file.sha256.clone();
+            let size = file.size;
+            let restored = {
+                let target = target.clone();
+                tokio::task::spawn_blocking(move || {
+                    cache.restore(&url, sha256.as_deref(), size, &target)
+                })
+                .await?
+            };
+            match restored {
+                Ok(Some(_)) => record_download_path(&download_dir, &file.url, &target).await?,
+                Ok(None) => {}
+                Err(err) => {
+                    tracing::warn!("Could not reuse cached download of {}: {err:#}", file.url);
+                }
+            }
+        }
+
         let (tx, mut rx) = mpsc::channel(32);
         tokio::task::spawn(download_files(
-            download_info.into_iter().map(|file| file.url),
+            download_info.into_iter().map(|file| DownloadFile {
+                url: file.url,
+                mirrors: file.mirrors,
+                size: file.size,
+                sha256: file.sha256,
+            }),
+            client,
             download_dir,
+            max_concurrent,
             tx,
             token.clone(),
         ));
 
-        let mut installer_path: Option<PathBuf> = None;
+        let mut downloaded_files = vec![];
 
         let mut next_progress_update = Instant::now();
 
@@ -166,23 +346,39 @@ impl InstallTask {
                         .send(InstallTaskProgress::DownloadStarted { content_length })
                         .await?;
                 }
-                DownloadMessage::Progress { progress_length } => {
+                DownloadMessage::Progress {
+                    progress_length,
+                    content_length,
+                    bytes_per_second,
+                    eta_secs,
+                } => {
                     let now = Instant::now();
                     if now >= next_progress_update {
                         sender
                             .send(InstallTaskProgress::DownloadProgress {
                                 finished_length: progress_length,
+                                content_length,
+                                bytes_per_second,
+                                eta_secs,
                             })
                             .await?;
                         next_progress_update =
                             now.checked_add(Duration::from_secs(1)).unwrap_or(now);
                     }
                 }
-                DownloadMessage::FileDone { path } => {
+                DownloadMessage::FileProgress(progress) => {
+                    sender
+                        .send(InstallTaskProgress::FileProgress(progress))
+                        .await?;
+                }
+                DownloadMessage::AttemptFailed(failure) => {
+                    sender
+                        .send(InstallTaskProgress::DownloadAttemptFailed(failure))
+                        .await?;
+                }
+                DownloadMessage::FileDone { url, path } => {
                     tracing::info!("Finished downloading file '{}'", path.display());
-                    if path.extension().map(|ext| ext == "exe").unwrap_or_default() {
-                        installer_path = Some(path);
-                    }
+                    downloaded_files.push((url, path));
                 }
                 DownloadMessage::Done => {
                     tracing::info!("Done downloading");
@@ -201,39 +397,133 @@ impl InstallTask {
             return Ok(());
         }
 
-        let Some(installer_path) = installer_path else {
-            return Err(anyhow!("Expected to find an executable installer in the download files, but it was not present."));
-        };
+        let downloaded_paths: HashMap<String, PathBuf> = downloaded_files.iter().cloned().collect();
+        let mut parts = vec![];
+        for (index, url) in installer_urls.iter().enumerate() {
+            let Some(path) = downloaded_paths.get(url) else {
+                return Err(anyhow!(
+                    "Part {} of the installer was not downloaded: {url}",
+                    index + 1
+                ));
+            };
+            parts.push(path.clone());
+        }
+
+        // Nothing is extracted until every downloaded file has been verified
+        for (url, path) in downloaded_files {
+            let file = manifest.get(&url).cloned().unwrap_or_default();
+            if let Err(err) =
+                signature::verify_artifact(server_key.as_ref(), &path, file.signature.as_ref())
+                    .await
+            {
+                let message = format!("{err:#}");
+                sender
+                    .send(InstallTaskProgress::Error(message.clone()))
+                    .await?;
+                return Err(anyhow!("Failed verifying game client download: {message}"));
+            }
 
-        tokio::task::spawn_blocking(move || Self::install_step(output_dir, sender, &installer_path))
+            let cache = cache.clone();
+            let stored = tokio::task::spawn_blocking(move || {
+                cache.store(&path, &url, file.sha256.as_deref())
+            })
+            .await?;
+            if let Err(err) = stored {
+                tracing::warn!("Could not add download to the cache: {err:#}");
+            }
+        }
+
+        tokio::task::spawn_blocking(move || Self::install_step(output_dir, sender, &parts, token))
             .await?
     }
 
     fn install_step(
         output_dir: PathBuf,
         sender: mpsc::Sender<InstallTaskProgress>,
-        installer_path: &PathBuf,
+        parts: &[PathBuf],
+        token: CancellationToken,
     ) -> anyhow::Result<()> {
         sender.blocking_send(InstallTaskProgress::Installing)?;
 
-        tracing::info!("Installing using {}", installer_path.to_string_lossy());
-        let mut child = Command::new(installer_path)
-            .args([
-                format!("-o{}", output_dir.to_string_lossy()),
-                "-y".to_string(),
-            ])
-            .spawn()?;
+        let installer_path = &parts[0];
+        tracing::info!(
+            "Installing from {} ({} parts)",
+            installer_path.display(),
+            parts.len()
+        );
+        let mut next_progress_update = Instant::now();
+        let result = extract::extract_archive(
+            parts,
+            &output_dir,
+            &token,
+            |finished_length, total_length| {
+                let now = Instant::now();
+                if now >= next_progress_update || finished_length == total_length {
+                    let _ = sender.blocking_send(InstallTaskProgress::Extracting {
+                        finished_length,
+                        total_length,
+                    });
+                    next_progress_update = now + PROGRESS_INTERVAL;
+                }
+            },
+        );
 
-        let out = child.wait()?;
+        if let Err(err) = result {
+            if token.is_cancelled() {
+                return Ok(());
+            }
 
-        if !out.success() {
             sender.blocking_send(InstallTaskProgress::Error(format!(
-                "Failed to install game using: {}",
-                installer_path.to_string_lossy()
+                "Failed to install game from {}: {err:#}",
+                installer_path.display()
             )))?;
             return Ok(());
         }
 
+        Self::check_installed(&output_dir, &sender)
+    }
+
+    fn copy_step(
+        output_dir: PathBuf,
+        sender: mpsc::Sender<InstallTaskProgress>,
+        from: &Path,
+        to: &Path,
+        token: CancellationToken,
+    ) -> anyhow::Result<()> {
+        sender.blocking_send(InstallTaskProgress::Installing)?;
+
+        tracing::info!("Copying client from {}", from.display());
+        let mut next_progress_update = Instant::now();
+        let result = extract::copy_directory(from, to, &token, |finished_length, total_length| {
+            let now = Instant::now();
+            if now >= next_progress_update || finished_length == total_length {
+                let _ = sender.blocking_send(InstallTaskProgress::Copying {
+                    finished_length,
+                    total_length,
+                });
+                next_progress_update = now + PROGRESS_INTERVAL;
+            }
+        });
+
+        if let Err(err) = result {
+            if token.is_cancelled() {
+                return Ok(());
+            }
+
+            sender.blocking_send(InstallTaskProgress::Error(format!(
+                "Failed to copy game from {}: {err:#}",
+                from.display()
+            )))?;
+            return Ok(());
+        }
+
+        Self::check_installed(&output_dir, &sender)
+    }
+
+    fn check_installed(
+        output_dir: &Path,
+        sender: &mpsc::Sender<InstallTaskProgress>,
+    ) -> anyhow::Result<()> {
         if !output_dir.join("FINAL FANTASY XI").exists() {
             sender.blocking_send(InstallTaskProgress::Error(
                 "Installed game did not result in a proper game setup.".to_string(),
@@ -245,3 +535,272 @@ impl InstallTask {
         Ok(())
     }
 }
+
+impl LocalSource {
+    /// Works out what `source` contains. It can be an installed client's directory, its
+    /// `FINAL FANTASY XI` directory, or a folder with the installer.
+    fn inspect(
+        source: &Path,
+        output_dir: &Path,
+        download_info: &DownloadInfo,
+    ) -> anyhow::Result<Self> {
+        if !source.is_dir() {
+            return Err(anyhow!("{} is not a folder.", source.display()));
+        }
+
+        if source.join("FINAL FANTASY XI").is_dir() {
+            check_client_copy(&source.join("FINAL FANTASY XI"), source, output_dir)?;
+            return Ok(Self::Client(source.to_path_buf(), output_dir.to_path_buf()));
+        }
+        if source.file_name() == Some(OsStr::new("FINAL FANTASY XI")) {
+            check_client_copy(source, source, output_dir)?;
+            return Ok(Self::Client(
+                source.to_path_buf(),
+                output_dir.join("FINAL FANTASY XI"),
+            ));
+        }
+
+        if !download_info.is_empty() {
+            let mut files = vec![];
+            for (index, url) in installer_parts(download_info)?.into_iter().enumerate() {
+                let name = filename::from_url(&url).unwrap_or_default();
+                let path = source.join(&name);
+                if name.is_empty() || !path.is_file() {
+                    return Err(anyhow!(
+                        "Part {} of the installer ({name}) is missing from {}.",
+                        index + 1,
+                        source.display()
+                    ));
+                }
+
+                let file = download_info.iter().find(|file| file.url == url).cloned();
+                files.push((path, file));
+            }
+            return Ok(Self::Installer(files));
+        }
+
+        let mut names = vec![];
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push((entry.file_name().to_string_lossy().to_string(), None));
+            }
+        }
+
+        let files = order_installer_parts(&names)
+            .with_context(|| format!("No installer found in {}", source.display()))?
+            .into_iter()
+            .map(|index| (source.join(&names[index].0), None))
+            .collect();
+        Ok(Self::Installer(files))
+    }
+}
+
+/// Checks that `client_dir` looks like an installed client, and that `from` can be copied into `output_dir`.
+fn check_client_copy(client_dir: &Path, from: &Path, output_dir: &Path) -> anyhow::Result<()> {
+    if !client_dir.join("ROM").is_dir() {
+        return Err(anyhow!(
+            "{} does not contain a complete client.",
+            client_dir.display()
+        ));
+    }
+
+    // Copying a directory into itself would never finish
+    fs::create_dir_all(output_dir)?;
+    let source = from.canonicalize()?;
+    let output = output_dir.canonicalize()?;
+    if output.starts_with(&source) || source.starts_with(&output) {
+        return Err(anyhow!(
+            "Can't copy the client from {}, because it overlaps the game directory.",
+            from.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the URLs of the installer archive in extraction order.
+pub fn installer_parts(download_info: &DownloadInfo) -> anyhow::Result<Vec<String>> {
+    let files: Vec<(String, Option<u32>)> = download_info
+        .iter()
+        .map(|file| (filename::from_url(&file.url).unwrap_or_default(), file.part))
+        .collect();
+
+    Ok(order_installer_parts(&files)?
+        .into_iter()
+        .map(|index| download_info[index].url.clone())
+        .collect())
+}
+
+/// Returns the indices of the installer files in extraction order, given their names and parts.
+/// A split archive is ordered by the files' `part`, or else by their numbered extensions, with a
+/// self-extracting `.exe` before numbered parts that start at `.002`.
+fn order_installer_parts(files: &[(String, Option<u32>)]) -> anyhow::Result<Vec<usize>> {
+    if files.iter().any(|(_, part)| part.is_some()) {
+        let mut parts: Vec<(u32, usize)> = files
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (_, part))| Some(((*part)?, index)))
+            .collect();
+        parts.sort_by_key(|(part, _)| *part);
+        return ordered_parts(parts);
+    }
+
+    let mut numbered: Vec<(u32, usize)> = files
+        .iter()
+        .enumerate()
+        .filter_map(|(index, (name, _))| Some((part_number(name)?, index)))
+        .collect();
+    numbered.sort_by_key(|(part, _)| *part);
+
+    let first_installer = files.iter().position(|(name, _)| is_installer(name));
+
+    match (numbered.first(), first_installer) {
+        (None, Some(index)) => Ok(vec![index]),
+        (None, None) => Err(anyhow!(
+            "Expected to find an installer archive, but it was not present."
+        )),
+        (Some((2, _)), Some(index)) if files[index].0.to_ascii_lowercase().ends_with(".exe") => {
+            numbered.insert(0, (1, index));
+            ordered_parts(numbered)
+        }
+        (Some(_), _) => ordered_parts(numbered),
+    }
+}
+
+/// Checks that the sorted parts are numbered 1, 2, 3, ... without gaps or duplicates.
+fn ordered_parts(parts: Vec<(u32, usize)>) -> anyhow::Result<Vec<usize>> {
+    let mut indices = vec![];
+    for (expected, (part, index)) in (1..).zip(parts) {
+        if part != expected {
+            return Err(anyhow!("Part {expected} of the installer is missing."));
+        }
+        indices.push(index);
+    }
+
+    if indices.is_empty() {
+        return Err(anyhow!("Part 1 of the installer is missing."));
+    }
+    Ok(indices)
+}
+
+/// The number of a split archive volume, such as 2 for `client.7z.002`.
+fn part_number(name: &str) -> Option<u32> {
+    let (_, extension) = name.rsplit_once('.')?;
+    if extension.len() < 3 || !extension.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    extension.parse().ok()
+}
+
+/// Installers are archives, or self-extracting archives.
+fn is_installer(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    [".exe", ".7z", ".zip"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(urls: &[&str]) -> DownloadInfo {
+        urls.iter()
+            .map(|url| FileInstallConfig {
+                url: url.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn orders_installer_parts() {
+        let base = "https://example.com/files/";
+        let url = |name: &str| format!("{base}{name}");
+
+        assert_eq!(
+            installer_parts(&files(&[&url("readme.txt"), &url("client.zip")])).unwrap(),
+            vec![url("client.zip")]
+        );
+        assert_eq!(
+            installer_parts(&files(&[
+                &url("client.7z.003"),
+                &url("client.7z.001"),
+                &url("client.7z.002"),
+            ]))
+            .unwrap(),
+            vec![
+                url("client.7z.001"),
+                url("client.7z.002"),
+                url("client.7z.003")
+            ]
+        );
+        assert_eq!(
+            installer_parts(&files(&[&url("client.7z.002"), &url("setup.exe")])).unwrap(),
+            vec![url("setup.exe"), url("client.7z.002")]
+        );
+
+        let missing = installer_parts(&files(&[&url("client.7z.001"), &url("client.7z.003")]));
+        assert_eq!(
+            missing.unwrap_err().to_string(),
+            "Part 2 of the installer is missing."
+        );
+
+        let mut numbered = files(&[&url("b.bin"), &url("a.bin")]);
+        numbered[0].part = Some(1);
+        numbered[1].part = Some(2);
+        assert_eq!(
+            installer_parts(&numbered).unwrap(),
+            vec![url("b.bin"), url("a.bin")]
+        );
+        numbered[1].part = Some(3);
+        assert!(installer_parts(&numbered).is_err());
+    }
+
+    #[test]
+    fn inspects_local_sources() {
+        let dir = std::env::temp_dir().join("xi-launcher-local-source-test");
+        let _ = fs::remove_dir_all(&dir);
+        let output_dir = dir.join("install");
+
+        let parts_dir = dir.join("usb");
+        fs::create_dir_all(&parts_dir).unwrap();
+        for name in ["client.7z.002", "setup.exe", "readme.txt"] {
+            fs::write(parts_dir.join(name), b"").unwrap();
+        }
+        let LocalSource::Installer(parts) =
+            LocalSource::inspect(&parts_dir, &output_dir, &vec![]).unwrap()
+        else {
+            panic!("expected installer parts");
+        };
+        let paths: Vec<PathBuf> = parts.into_iter().map(|(path, _)| path).collect();
+        assert_eq!(
+            paths,
+            vec![parts_dir.join("setup.exe"), parts_dir.join("client.7z.002")]
+        );
+
+        // Manifest parts are looked up by name
+        let manifest = files(&[
+            "https://example.com/client.7z.002",
+            "https://example.com/client.7z.001",
+        ]);
+        let err = LocalSource::inspect(&parts_dir, &output_dir, &manifest).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Part 1 of the installer (client.7z.001) is missing"));
+
+        let client_dir = dir.join("old/FINAL FANTASY XI");
+        fs::create_dir_all(&client_dir).unwrap();
+        assert!(LocalSource::inspect(&client_dir, &output_dir, &vec![]).is_err());
+
+        fs::create_dir_all(client_dir.join("ROM")).unwrap();
+        assert!(matches!(
+            LocalSource::inspect(&dir.join("old"), &output_dir, &vec![]).unwrap(),
+            LocalSource::Client(from, to) if from == dir.join("old") && to == output_dir
+        ));
+        assert!(LocalSource::inspect(&dir.join("old"), &dir.join("old/new"), &vec![]).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,190 +1,353 @@
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Arc, time::Instant};
 
 use anyhow::anyhow;
 use futures_util::StreamExt;
-use reqwest::Response;
+use minisign_verify::PublicKey;
+use reqwest::{Client, Response};
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 use specta::Type;
 use tauri::ipc::Channel;
 use tokio::{fs::File, io::AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    check_game::{part_needs_update, VersionsInfo},
+    bandwidth,
+    check_game::{get_component_change, ArchiveKind, ComponentBase, ComponentInfo, VersionsInfo},
     config::profiles::Profile,
+    download_cache::DownloadCache,
+    extract,
+    file_download::{RateTracker, PROGRESS_INTERVAL},
+    http, signature,
+    version::VersionChange,
 };
 
 #[derive(Debug, Clone, Serialize, Type)]
 #[serde(tag = "event", content = "data")]
 pub enum UpdateTaskMessage {
     DownloadPending {
-        id: &'static str,
+        id: String,
     },
 
     DownloadStarted {
-        id: &'static str,
+        id: String,
         content_length: u64,
     },
 
     DownloadProgress {
-        id: &'static str,
+        id: String,
         finished_length: u64,
+        total_length: Option<u64>,
+        bytes_per_second: u64,
+        eta_secs: Option<u64>,
     },
 
     DownloadFinished {
-        id: &'static str,
+        id: String,
     },
 
     UnpackPending {
-        id: &'static str,
+        id: String,
     },
 
     UnpackFinished {
-        id: &'static str,
+        id: String,
     },
 
     FailedSpecific {
-        id: &'static str,
+        id: String,
     },
 
     Done,
 }
 
+/// Returns the versions of the components that weren't downgraded, since a downgrade wasn't allowed.
 pub async fn update_with_versions(
     profile: &Profile,
     versions_info: VersionsInfo,
+    allow_downgrade: bool,
+    cache: &Arc<DownloadCache>,
     channel: Channel<UpdateTaskMessage>,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<BTreeMap<String, String>> {
     let server_filename = profile.get_server_filename();
-
-    let bootloader_base_path = profile
-        .get_bootloader_path()
-        .ok_or(anyhow!("Could not determine bootloader path"))?;
-
-    let bootloader_versions_path = bootloader_base_path.join("version.txt");
-    if let Some(info) =
-        part_needs_update(&versions_info.bootloader, &bootloader_versions_path).await
-    {
-        tracing::info!(
-            "Found bootloader update for {} with version {}",
-            server_filename,
-            info.version
-        );
-        fs::create_dir_all(&bootloader_base_path)?;
-        let bootloader_path = bootloader_base_path.join("xiloader.exe");
-
-        let id = "Bootloader";
-        channel.send(UpdateTaskMessage::DownloadPending { id })?;
-
-        tracing::info!(
-            "Downloading bootloader for {} with version {}",
-            server_filename,
-            info.version
-        );
-        download_file_to(id, &bootloader_path, &info.url, &channel).await?;
+    let server_key = signature::get_server_key(profile)?;
+    // Components are usually hosted by the server itself, so they need its CA certificate too
+    let client = http::server_client(profile)?;
+    let mut declined_downgrades = BTreeMap::new();
+
+    for component in versions_info.all_components() {
+        let version_path = component.get_version_path(profile)?;
+        match get_component_change(profile, &component, &version_path).await {
+            VersionChange::Unchanged => continue,
+            VersionChange::Upgrade => {
+                tracing::info!(
+                    "Found {} update for {} with version {}",
+                    component.name,
+                    server_filename,
+                    component.version
+                );
+            }
+            VersionChange::Downgrade if allow_downgrade => {
+                tracing::info!(
+                    "Downgrading {} for {} to version {}",
+                    component.name,
+                    server_filename,
+                    component.version
+                );
+            }
+            VersionChange::Downgrade => {
+                tracing::warn!(
+                    "Skipping downgrade of {} for {} to version {}",
+                    component.name,
+                    server_filename,
+                    component.version
+                );
+                declined_downgrades.insert(component.name.clone(), component.version.clone());
+                continue;
+            }
+        }
+
+        if let Err(err) = update_component(
+            profile,
+            &component,
+            &client,
+            server_key.as_ref(),
+            cache,
+            &channel,
+        )
+        .await
+        {
+            channel.send(UpdateTaskMessage::FailedSpecific {
+                id: component.name.clone(),
+            })?;
+            return Err(err);
+        }
 
         tracing::info!(
-            "Writing bootloader version file for {} with version {}",
+            "Writing {} version file for {} with version {}",
+            component.name,
             server_filename,
-            info.version
+            component.version
         );
-        let mut versions_file = File::create(bootloader_versions_path).await?;
-        versions_file.write_all(info.version.as_bytes()).await?;
+        if let Some(parent) = version_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut versions_file = File::create(version_path).await?;
+        versions_file
+            .write_all(component.version.as_bytes())
+            .await?;
         versions_file.flush().await?;
     }
 
-    let dats_base_path = profile
-        .get_pivot_dat_path()
-        .ok_or(anyhow!("Could not determine XIPivot DAT path"))?;
-
-    let dats_versions_path = dats_base_path.join("version.txt");
-    if let Some(info) = part_needs_update(&versions_info.dats, &dats_versions_path).await {
-        tracing::info!(
-            "Found DATs update for {} with version {}",
-            server_filename,
-            info.version
-        );
-
-        // Clean up previous DATs
-        let _ = fs::remove_dir_all(&dats_base_path);
+    tracing::info!("Update complete");
+    channel.send(UpdateTaskMessage::Done)?;
+    Ok(declined_downgrades)
+}
 
-        // Download archive containing new DATs
-        fs::create_dir_all(&dats_base_path)?;
-        let dats_archive_path = dats_base_path.join("dats.7z");
+async fn update_component(
+    profile: &Profile,
+    component: &ComponentInfo,
+    client: &Client,
+    server_key: Option<&PublicKey>,
+    cache: &Arc<DownloadCache>,
+    channel: &Channel<UpdateTaskMessage>,
+) -> anyhow::Result<()> {
+    let id = component.name.clone();
+    let target_dir = component.get_target_dir(profile)?;
+    let file_name = component.get_file_name()?;
+
+    // The game, frontend and bootloader directories hold much more than the component, such as
+    // the client, addons, versions and login tokens. A server's DATs directory is its own to clean.
+    if component.clean
+        && target_dir == component.get_base_dir(profile)?
+        && component.base != ComponentBase::Dats
+    {
+        return Err(anyhow!(
+            "Refusing to clean the entire base directory for {}.",
+            component.name
+        ));
+    }
 
-        tracing::info!(
-            "Downloading DATs for {} with version {}",
-            server_filename,
-            info.version
+    // Download next to the target directory, so it can be cleaned without losing the download
+    let staging_dir = target_dir
+        .parent()
+        .ok_or(anyhow!(
+            "Invalid target directory for {}: {}",
+            component.name,
+            target_dir.display()
+        ))?
+        .to_path_buf();
+    fs::create_dir_all(&staging_dir)?;
+    let download_path = staging_dir.join(format!("{file_name}.download"));
+
+    channel.send(UpdateTaskMessage::DownloadPending { id: id.clone() })?;
+
+    // Another profile may have downloaded the same file already
+    let cached = {
+        let cache = cache.clone();
+        let url = component.url.clone();
+        let sha256 = component.sha256.clone();
+        let size = component.size;
+        let path = download_path.clone();
+        tokio::task::spawn_blocking(move || cache.restore(&url, sha256.as_deref(), size, &path))
+            .await?
+            .inspect_err(|err| tracing::warn!("Could not reuse cached download: {err:#}"))
+            .ok()
+            .flatten()
+    };
+
+    let downloaded = match cached {
+        Some(sha256) => Ok((fs::metadata(&download_path)?.len(), sha256)),
+        None => {
+            tracing::info!("Downloading {} from {}", component.name, component.url);
+            download_file_to(&id, client, &download_path, &component.url, channel).await
+        }
+    };
+
+    let result: anyhow::Result<String> = async {
+        let (length, sha256) = downloaded?;
+        verify_download(component, length, &sha256)?;
+        signature::verify_artifact(server_key, &download_path, component.signature.as_ref())
+            .await?;
+        Ok(sha256)
+    }
+    .await;
+
+    let sha256 = match result {
+        Ok(sha256) => sha256,
+        Err(err) => {
+            let _ = fs::remove_file(&download_path);
+            return Err(err);
+        }
+    };
+    channel.send(UpdateTaskMessage::DownloadFinished { id: id.clone() })?;
+
+    let stored = {
+        let cache = cache.clone();
+        let url = component.url.clone();
+        let sha256 = sha256.clone();
+        let path = download_path.clone();
+        tokio::task::spawn_blocking(move || cache.store(&path, &url, Some(&sha256))).await?
+    };
+    if let Err(err) = stored {
+        tracing::warn!(
+            "Could not add {} to the download cache: {err:#}",
+            component.name
         );
-        let id = "DATs";
-        channel.send(UpdateTaskMessage::DownloadPending { id })?;
-        download_file_to(id, &dats_archive_path, &info.url, &channel).await?;
+    }
 
-        tracing::info!("Unpacking DATs");
-        channel.send(UpdateTaskMessage::UnpackPending { id })?;
-        sevenz_rust::decompress_file(&dats_archive_path, dats_base_path)?;
-        let _ = fs::remove_file(dats_archive_path);
-        channel.send(UpdateTaskMessage::UnpackFinished { id })?;
+    if component.clean {
+        let _ = fs::remove_dir_all(&target_dir);
+    }
+    fs::create_dir_all(&target_dir)?;
+
+    let install_result = match component.archive {
+        ArchiveKind::None => {
+            fs::rename(&download_path, target_dir.join(&file_name)).map_err(anyhow::Error::from)
+        }
+        ArchiveKind::SevenZip | ArchiveKind::Zip => {
+            tracing::info!("Unpacking {}", component.name);
+            channel.send(UpdateTaskMessage::UnpackPending { id: id.clone() })?;
+            let parts = [download_path.clone()];
+            let unpack_dir = target_dir.clone();
+            // Unpacking can take a while, so it shouldn't block the async workers. Entries that
+            // would end up outside the target directory are rejected while extracting.
+            let result = tokio::task::spawn_blocking(move || {
+                extract::extract_archive(&parts, &unpack_dir, &CancellationToken::new(), |_, _| {})
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result);
+            channel.send(UpdateTaskMessage::UnpackFinished { id: id.clone() })?;
+            result
+        }
+    };
+
+    let _ = fs::remove_file(&download_path);
+    install_result
+}
 
-        tracing::info!(
-            "Writing version DATs file for {} with version {}",
-            server_filename,
-            info.version
-        );
-        let mut versions_file = File::create(dats_versions_path).await?;
-        versions_file.write_all(info.version.as_bytes()).await?;
-        versions_file.flush().await?;
+fn verify_download(component: &ComponentInfo, length: u64, sha256: &str) -> anyhow::Result<()> {
+    if let Some(expected_size) = component.size {
+        if expected_size != length {
+            return Err(anyhow!(
+                "Downloaded {} has a size of {length} bytes, but expected {expected_size} bytes.",
+                component.name
+            ));
+        }
+    }
+
+    if let Some(expected_sha256) = &component.sha256 {
+        if !expected_sha256.eq_ignore_ascii_case(sha256) {
+            return Err(anyhow!(
+                "Downloaded {} has checksum {sha256}, but expected {expected_sha256}.",
+                component.name
+            ));
+        }
     }
 
-    tracing::info!("Update complete");
-    channel.send(UpdateTaskMessage::Done)?;
     Ok(())
 }
 
+/// Downloads the URL to the given path, returning the length and SHA-256 of the downloaded file.
 async fn download_file_to(
-    id: &'static str,
+    id: &str,
+    client: &Client,
     output_path: &PathBuf,
     url: &str,
     channel: &Channel<UpdateTaskMessage>,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<(u64, String)> {
     let file = File::create(output_path).await?;
 
-    let response = reqwest::get(url).await?;
+    let response = client.get(url).send().await?.error_for_status()?;
 
     channel.send(UpdateTaskMessage::DownloadStarted {
-        id,
+        id: id.to_string(),
         content_length: response.content_length().unwrap_or_default(),
     })?;
 
-    if let Err(err) = stream_to_file(id, response, file, &channel).await {
-        tracing::error!("Error while streaming to file: {err:?}");
-        channel.send(UpdateTaskMessage::FailedSpecific { id })?;
-    } else {
-        channel.send(UpdateTaskMessage::DownloadFinished { id })?;
-    }
-
-    Ok(())
+    stream_to_file(id, response, file, &channel)
+        .await
+        .inspect_err(|err| tracing::error!("Error while streaming to file: {err:?}"))
 }
 
 async fn stream_to_file(
-    id: &'static str,
+    id: &str,
     response: Response,
     mut file: File,
     channel: &Channel<UpdateTaskMessage>,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<(u64, String)> {
+    let total_length = response.content_length();
     let mut stream = response.bytes_stream();
+    let mut hasher = Sha256::new();
+    let mut rate = RateTracker::default();
+    let mut last_update: Option<Instant> = None;
 
     let mut finished_length = 0u64;
     while let Some(chunk) = stream.next().await {
         let chunk = chunk?;
+        bandwidth::limiter().acquire(chunk.len() as u64).await;
         file.write_all(&chunk).await?;
+        hasher.update(&chunk);
         finished_length += chunk.len() as u64;
-
-        channel.send(UpdateTaskMessage::DownloadProgress {
-            id,
-            finished_length,
-        })?;
+        rate.add(chunk.len() as u64);
+
+        let now = Instant::now();
+        let finished = total_length == Some(finished_length);
+        if finished || last_update.is_none_or(|last| now - last >= PROGRESS_INTERVAL) {
+            last_update = Some(now);
+            channel.send(UpdateTaskMessage::DownloadProgress {
+                id: id.to_string(),
+                finished_length,
+                total_length,
+                bytes_per_second: rate.bytes_per_second(),
+                eta_secs: total_length
+                    .and_then(|total| rate.eta_secs(total.saturating_sub(finished_length))),
+            })?;
+        }
     }
 
-    Ok(())
+    file.flush().await?;
+
+    Ok((finished_length, format!("{:x}", hasher.finalize())))
 }
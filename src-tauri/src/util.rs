@@ -1,4 +1,7 @@
-use std::{fs::File, path::PathBuf};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
 
 use serde::de::DeserializeOwned;
 
@@ -11,3 +14,72 @@ where
         .and_then(|file| serde_json::from_reader(file).ok())
         .unwrap_or_default()
 }
+
+/// Lists the names of the addon folders found in `<base_dir>/addons`.
+pub fn list_addons(base_dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut addons = vec![];
+    for entry in fs::read_dir(base_dir.join("addons"))? {
+        entry
+            .ok()
+            .filter(|e| e.file_type().map(|e| e.is_dir()).unwrap_or_default())
+            .and_then(|e| e.file_name().into_string().ok())
+            .map(|name| addons.push(name));
+    }
+
+    Ok(addons)
+}
+
+/// Lists the names of the plugin DLLs found in `<base_dir>/plugins`.
+pub fn list_plugins(base_dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut plugins = vec![];
+    for entry in fs::read_dir(base_dir.join("plugins"))? {
+        entry
+            .ok()
+            .filter(|e| e.file_type().map(|e| e.is_file()).unwrap_or_default())
+            .filter(|e| {
+                e.path()
+                    .extension()
+                    .map(|ext| ext == "dll")
+                    .unwrap_or_default()
+            })
+            .and_then(|e| {
+                e.path()
+                    .file_stem()
+                    .and_then(|os_str| os_str.to_str())
+                    .map(|s| s.to_owned())
+            })
+            .map(|name| plugins.push(name));
+    }
+
+    Ok(plugins)
+}
+
+/// Moves a directory, falling back to copying and removing it if a rename isn't possible,
+/// e.g. when moving across drives.
+pub fn move_dir_all(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    copy_dir_all(from, to)?;
+    fs::remove_dir_all(from)
+}
+
+pub fn copy_dir_all(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+
+    Ok(())
+}
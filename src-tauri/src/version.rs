@@ -0,0 +1,109 @@
+use std::cmp::Ordering;
+
+use semver::Version;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionChange {
+    Unchanged,
+    Upgrade,
+    Downgrade,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParsedVersion {
+    Semver(Version),
+    /// Dot or dash separated numbers, such as `1.2` or date stamps like `2024-05-01` and `20240501`.
+    Numeric(Vec<u64>),
+    Text(String),
+}
+
+fn parse_version(version: &str) -> ParsedVersion {
+    let version = version.trim();
+    let stripped = version.strip_prefix(['v', 'V']).unwrap_or(version);
+
+    if let Ok(semver) = Version::parse(stripped) {
+        return ParsedVersion::Semver(semver);
+    }
+
+    let numbers: Option<Vec<u64>> = stripped
+        .split(['.', '-', '_'])
+        .map(|part| part.parse().ok())
+        .collect();
+
+    match numbers {
+        Some(numbers) if !numbers.is_empty() => ParsedVersion::Numeric(numbers),
+        _ => ParsedVersion::Text(version.to_string()),
+    }
+}
+
+fn compare_numeric(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    let pad = |numbers: &[u64]| {
+        let mut numbers = numbers.to_vec();
+        numbers.resize(len, 0);
+        numbers
+    };
+
+    pad(a).cmp(&pad(b))
+}
+
+/// Compares the installed version of a component with the one available from the server.
+/// Versions that can't be ordered are treated as an upgrade whenever they differ.
+pub fn compare_versions(installed: &str, available: &str) -> VersionChange {
+    let ordering = match (parse_version(installed), parse_version(available)) {
+        (ParsedVersion::Semver(a), ParsedVersion::Semver(b)) => Some(a.cmp(&b)),
+        (ParsedVersion::Numeric(a), ParsedVersion::Numeric(b)) => Some(compare_numeric(&a, &b)),
+        (ParsedVersion::Semver(a), ParsedVersion::Numeric(b)) if a.pre.is_empty() => {
+            Some(compare_numeric(&[a.major, a.minor, a.patch], &b))
+        }
+        (ParsedVersion::Numeric(a), ParsedVersion::Semver(b)) if b.pre.is_empty() => {
+            Some(compare_numeric(&a, &[b.major, b.minor, b.patch]))
+        }
+        (a, b) => (a == b).then_some(Ordering::Equal),
+    };
+
+    match ordering {
+        Some(Ordering::Equal) => VersionChange::Unchanged,
+        Some(Ordering::Greater) => VersionChange::Downgrade,
+        Some(Ordering::Less) | None => VersionChange::Upgrade,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_versions, VersionChange};
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(
+            compare_versions("1.2.3\n", "1.2.3"),
+            VersionChange::Unchanged
+        );
+        assert_eq!(
+            compare_versions("v1.2.3", "1.2.3"),
+            VersionChange::Unchanged
+        );
+        assert_eq!(compare_versions("1.2.3", "1.10.0"), VersionChange::Upgrade);
+        assert_eq!(
+            compare_versions("1.2.3", "1.2.3-beta"),
+            VersionChange::Downgrade
+        );
+        assert_eq!(compare_versions("1.2", "1.2.0"), VersionChange::Unchanged);
+        assert_eq!(
+            compare_versions("2024-05-01", "2024-04-30"),
+            VersionChange::Downgrade
+        );
+        assert_eq!(
+            compare_versions("20240501", "20240502"),
+            VersionChange::Upgrade
+        );
+        assert_eq!(
+            compare_versions("release-a", "release-b"),
+            VersionChange::Upgrade
+        );
+        assert_eq!(
+            compare_versions("release-a", "release-a "),
+            VersionChange::Unchanged
+        );
+    }
+}
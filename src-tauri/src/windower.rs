@@ -1,10 +1,10 @@
-use std::{fs::File, io::Write, os::windows::process::CommandExt, process::Command};
+use std::{fs::File, io::Write, os::windows::process::CommandExt, path::Path, process::Command};
 
 use anyhow::{anyhow, Context};
 
 use edit_xml::{Document, Element};
 
-use crate::config::profiles::{AuthKind, Profile};
+use crate::config::profiles::{AuthKind, Profile, Resolution};
 
 pub async fn launch_game(
     profile: &Profile,
@@ -119,17 +119,7 @@ pub fn update_windower_profile(
     // Update args for profile if necessary
     let args_text = args.join(" ");
 
-    if let Some(xml_args) = xml_profile.find(&doc, "args") {
-        if xml_args.text_content(&doc) != args_text {
-            xml_args.set_text_content(&mut doc, args_text);
-            needs_update = true;
-        }
-    } else {
-        Element::build("args")
-            .add_text(args_text)
-            .push_to(&mut doc, xml_profile);
-        needs_update = true;
-    }
+    needs_update |= set_child_text(&mut doc, xml_profile, "args", &args_text);
 
     // Update bootloader path if necessary
     let bootloader_path = profile
@@ -139,25 +129,87 @@ pub fn update_windower_profile(
 
     let bootloader_str = bootloader_path.as_os_str().to_str().unwrap_or_default();
 
-    if let Some(xml_executable) = xml_profile.find(&doc, "executable") {
-        if xml_executable.text_content(&doc) != bootloader_str {
-            xml_executable.set_text_content(&mut doc, bootloader_str);
-            needs_update = true;
+    needs_update |= set_child_text(&mut doc, xml_profile, "executable", bootloader_str);
+
+    if needs_update {
+        write_settings(&doc, &settings_path)?;
+    }
+
+    Ok(())
+}
+
+/// Makes sure a Windower profile with the given name exists in settings.xml,
+/// and carries the resolution of the launcher profile over to it.
+pub fn sync_windower_profile(profile: &Profile, profile_name: &str) -> anyhow::Result<()> {
+    let settings_path = profile.install.try_get_windower_dir()?.join("settings.xml");
+    let mut doc = Document::parse_file(&settings_path)?;
+
+    if locate_profile_with_name(&doc, profile_name).is_none() {
+        let settings = doc.container().find(&doc, "settings").ok_or(anyhow!(
+            "Windower settings.xml is missing a settings element."
+        ))?;
+
+        Element::build("profile")
+            .attribute("name", profile_name)
+            .push_to(&mut doc, settings);
+    }
+
+    let xml_profile = locate_profile_with_name(&doc, profile_name).ok_or(anyhow!(
+        "Could not create Windower profile called '{profile_name}'."
+    ))?;
+
+    let width = profile.resolution.width.to_string();
+    let height = profile.resolution.height.to_string();
+    set_child_text(&mut doc, xml_profile, "x_resolution", &width);
+    set_child_text(&mut doc, xml_profile, "y_resolution", &height);
+
+    write_settings(&doc, &settings_path)
+}
+
+/// Reads the resolution of a Windower profile in settings.xml, if it has one.
+pub fn get_windower_profile_resolution(
+    profile: &Profile,
+    profile_name: &str,
+) -> anyhow::Result<Option<Resolution>> {
+    let settings_path = profile.install.try_get_windower_dir()?.join("settings.xml");
+    let doc = Document::parse_file(&settings_path)?;
+
+    let Some(xml_profile) = locate_profile_with_name(&doc, profile_name) else {
+        return Ok(None);
+    };
+
+    let parse_child = |name: &str| {
+        xml_profile
+            .find(&doc, name)
+            .and_then(|element| element.text_content(&doc).trim().parse::<u16>().ok())
+    };
+
+    Ok(parse_child("x_resolution")
+        .zip(parse_child("y_resolution"))
+        .map(|(width, height)| Resolution { width, height }))
+}
+
+/// Sets the text of the child element with the given name, creating it if necessary.
+/// Returns whether anything was changed.
+fn set_child_text(doc: &mut Document, parent: Element, name: &str, text: &str) -> bool {
+    if let Some(child) = parent.find(doc, name) {
+        if child.text_content(doc) == text {
+            return false;
         }
+        child.set_text_content(doc, text);
     } else {
-        Element::build("executable")
-            .add_text(bootloader_str)
-            .push_to(&mut doc, xml_profile);
-        needs_update = true;
+        Element::build(name).add_text(text).push_to(doc, parent);
     }
 
-    if needs_update {
-        tracing::info!("About to update Windower settings.xml");
-        let mut settings_file = File::create(&settings_path)?;
-        settings_file.write_all(doc.write_str()?.as_bytes())?;
-        settings_file.flush()?;
-        tracing::info!("Updated Windower settings.xml");
-    }
+    true
+}
+
+fn write_settings(doc: &Document, settings_path: &Path) -> anyhow::Result<()> {
+    tracing::info!("About to update Windower settings.xml");
+    let mut settings_file = File::create(settings_path)?;
+    settings_file.write_all(doc.write_str()?.as_bytes())?;
+    settings_file.flush()?;
+    tracing::info!("Updated Windower settings.xml");
 
     Ok(())
 }
@@ -45,6 +45,78 @@ async moveProfile(fromIndex: number, toIndex: number) : Promise<Result<null, str
     else return { status: "error", error: e  as any };
 }
 },
+async convertProfile(id: number, toWindower: boolean) : Promise<Result<ConversionReport, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("convert_profile", { id, toWindower }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getServerListUrls() : Promise<Result<string[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_server_list_urls") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async saveServerListUrls(urls: string[]) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("save_server_list_urls", { urls }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getBandwidthSettings() : Promise<Result<BandwidthSettings, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_bandwidth_settings") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async saveBandwidthSettings(settings: BandwidthSettings) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("save_bandwidth_settings", { settings }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getHttpSettings() : Promise<Result<HttpSettings, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_http_settings") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async saveHttpSettings(settings: HttpSettings) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("save_http_settings", { settings }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getServerDirectory() : Promise<Result<ServerDirectoryFeed[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_server_directory") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async createProfileFromDirectory(entry: ServerDirectoryEntry, templateId: number | null) : Promise<Result<number, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("create_profile_from_directory", { entry, templateId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async shouldRequestPassword(id: number) : Promise<Result<boolean, string>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("should_request_password", { id }) };
@@ -61,17 +133,73 @@ async checkLaunchProfile(id: number) : Promise<Result<LaunchStatus, string>> {
     else return { status: "error", error: e  as any };
 }
 },
-async installGameForProfile(id: number, downloadInfo: FileInstallConfig[], channel: TAURI_CHANNEL<InstallTaskProgress>) : Promise<Result<null, string>> {
+async refreshLaunchProfile(id: number) : Promise<Result<LaunchStatus, string>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("install_game_for_profile", { id, downloadInfo, channel }) };
+    return { status: "ok", data: await TAURI_INVOKE("refresh_launch_profile", { id }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
 }
 },
-async updateProfileServerFiles(id: number, channel: TAURI_CHANNEL<UpdateTaskMessage>) : Promise<Result<null, string>> {
+async getReadinessReport(id: number) : Promise<Result<ReadinessReport, string>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("update_profile_server_files", { id, channel }) };
+    return { status: "ok", data: await TAURI_INVOKE("get_readiness_report", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getServerStatus(channel: TAURI_CHANNEL<ServerStatus>) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_server_status", { channel }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async diagnoseServer(id: number) : Promise<Result<ServerDiagnosis, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("diagnose_server", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getServerNews(id: number) : Promise<Result<ServerNews, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_server_news", { id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async markServerNewsRead(id: number, itemIds: string[]) : Promise<Result<ServerNews, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("mark_server_news_read", { id, itemIds }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async installGameForProfile(id: number, downloadInfo: FileInstallConfig[], localSource: string | null, channel: TAURI_CHANNEL<InstallTaskProgress>) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("install_game_for_profile", { id, downloadInfo, localSource, channel }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async updateProfileServerFiles(id: number, allowDowngrade: boolean, channel: TAURI_CHANNEL<UpdateTaskMessage>) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("update_profile_server_files", { id, allowDowngrade, channel }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async pinComponentVersion(id: number, component: string, version: string | null) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("pin_component_version", { id, component, version }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
@@ -93,6 +221,14 @@ async cancelPossibleProfileTask(id: number) : Promise<Result<null, string>> {
     else return { status: "error", error: e  as any };
 }
 },
+async pruneDownloadCache(maxMib: number | null) : Promise<Result<PruneReport, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("prune_download_cache", { maxMib }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async listAshitaAddons(ashitaDirectory: string) : Promise<Result<string[], string>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("list_ashita_addons", { ashitaDirectory }) };
@@ -129,22 +265,280 @@ async configureGamepad(gameDirectory: string) : Promise<Result<null, string>> {
 
 /** user-defined types **/
 
+export type ArchiveKind = "none" | "7z" | "zip"
+/**
+ * A failed request for a file, which is retried or continued at another mirror.
+ */
+export type AttemptFailure = { 
+/**
+ * The primary URL of the file.
+ */
+url: string; 
+/**
+ * The URL that was requested, which is one of the file's mirrors.
+ */
+source: string; attempt: number; message: string; 
+/**
+ * When the same source is retried. `None` when moving on to the next mirror, or giving up.
+ */
+retry_in_ms: number | null }
 export type AuthKind = "Token" | "Password" | "ManualPassword"
-export type FileInstallConfig = { url: string }
+/**
+ * Local hours from `start_hour` up to `end_hour`, wrapping past midnight if `end_hour` is earlier.
+ */
+export type BandwidthSchedule = { start_hour: number; end_hour: number }
+export type BandwidthSettings = { 
+/**
+ * Combined download speed of all downloads in KiB/s, or unlimited if not set.
+ */
+limit_kib_per_sec?: number | null; 
+/**
+ * Hours of the day in which downloads are not limited.
+ */
+unlimited_hours?: BandwidthSchedule | null }
+/**
+ * Client version a server declares in its versions manifest.
+ */
+export type ClientRequirement = { min_version?: string | null; exact_version?: string | null; 
+/**
+ * Minimum number of VTABLE entries, for clients without a version file.
+ */
+min_table_entries?: number | null; 
+/**
+ * Blocks launching on a mismatch, instead of only warning about it.
+ */
+required?: boolean }
+/**
+ * The client version, as far as it can be read from the game files.
+ */
+export type ClientVersion = { 
+/**
+ * Version from the client's version file, such as `30240404_0`.
+ */
+version: string | null; 
+/**
+ * Number of DAT files listed in the VTABLEs of all ROM directories, which grows with every client patch.
+ */
+table_entries: number }
+export type ComponentBase = "frontend" | "bootloader" | "dats" | "game"
+export type ComponentInfo = { name: string; url: string; version: string; size?: number | null; 
+/**
+ * Required, except for the legacy `bootloader` and `dats` entries.
+ */
+sha256?: string | null; 
+/**
+ * Detached minisign signature of the download.
+ */
+signature?: string | null; archive?: ArchiveKind; base?: ComponentBase; 
+/**
+ * Directory relative to [ComponentInfo::base] that the component is installed into.
+ */
+target_dir?: string | null; 
+/**
+ * Name to store a non-archive download as. Defaults to the last segment of the URL.
+ */
+file_name?: string | null; 
+/**
+ * Removes the target directory before installing the component.
+ */
+clean?: boolean }
+export type ConversionReport = { moved_bootloader: boolean; moved_dats: boolean; 
+/**
+ * Enabled addons that were not found in the target frontend.
+ */
+unmatched_addons: string[]; 
+/**
+ * Enabled plugins that were not found in the target frontend.
+ */
+unmatched_plugins: string[]; 
+/**
+ * Extra pivots that were not found in the DATs folder of the target frontend.
+ */
+unmatched_pivots: string[] }
+export type EndpointDiagnosis = { endpoint: string; url: string | null; 
+/**
+ * Whether the server provides the endpoint at all. Both endpoints are optional.
+ */
+found: boolean; error: ServerErrorDetails | null; problems: string[] }
+export type FileInstallConfig = { url: string; 
+/**
+ * Other URLs serving the exact same file, which are used when `url` fails.
+ */
+mirrors?: string[]; 
+/**
+ * Expected size in bytes, checked while downloading.
+ */
+size?: number | null; 
+/**
+ * Expected SHA-256 of the file, checked while downloading and before reusing a downloaded file.
+ */
+sha256?: string | null; 
+/**
+ * Detached minisign signature of the file.
+ */
+signature?: string | null; 
+/**
+ * Position of the file in a split installer archive, starting at 1.
+ * Without it, parts are ordered by their `.001`, `.002`, ... extensions.
+ */
+part?: number | null }
+export type FileProgress = { url: string; name: string; finished_length: number; total_length: number | null; bytes_per_second: number; eta_secs: number | null }
+export type FixAction = "SetGameDirectory" | "InstallGame" | "UpdateClient" | "SetAshitaDirectory" | "SetWindowerDirectory" | "SetWindowerProfile" | "UpdateServerFiles" | "InstallPivot" | "SetAccountName" | "EnterPassword" | "SaveProfile" | "CheckPermissions"
+/**
+ * Settings for every HTTP request the launcher makes.
+ */
+export type HttpSettings = { proxy?: ProxySettings | null; 
+/**
+ * How long to wait for a connection to be established.
+ */
+connect_timeout_secs?: number | null; 
+/**
+ * How long to wait for more data on an open connection, so stalled downloads are retried.
+ */
+read_timeout_secs?: number | null; 
+/**
+ * PEM files with additional root certificates to trust, for proxies that intercept TLS.
+ */
+extra_root_certificates?: string[] }
 export type InstallConfig = { directory?: string | null; 
 /**
  * If None, and [InstallConfig::directory] is set, then [InstallConfig::directory] is assumed to have Ashita in it as well.
  */
 ashita_directory?: string | null; windower_directory?: string | null }
-export type InstallTaskProgress = { event: "Pending" } | { event: "DownloadStarted"; data: { content_length: number } } | { event: "DownloadProgress"; data: { finished_length: number } } | { event: "Installing" } | { event: "Complete" } | { event: "Error"; data: string }
-export type LaunchStatus = { type: "NeedsGameDir" } | { type: "NeedsInstall" } | { type: "NeedsAndCanInstall"; data: { download_info: FileInstallConfig[] } } | { type: "NeedsAshita" } | { type: "NeedsWindower" } | { type: "NeedsUpdate"; data: { versions_info: VersionsInfo } } | { type: "NeedsPassword" } | { type: "Ready" }
-export type Profile = { id: number; name?: string | null; server?: string | null; server_nickname?: string | null; server_info_addr?: string | null; use_windower?: boolean; is_retail?: boolean; install?: InstallConfig; account_name?: string | null; password?: string | null; auth_kind?: AuthKind; manual_auth?: boolean; hairpin?: boolean; enable_gamepad?: boolean; enable_gamepad_background?: boolean; resolution?: Resolution; background_resolution?: Resolution; menu_resolution?: Resolution; start_pos_x?: number; start_pos_y?: number; enabled_addons?: string[] | null; enabled_plugins?: string[] | null; extra_pivots?: string[]; windower_profile?: string | null }
+export type InstallTaskProgress = { event: "Pending" } | { event: "DownloadStarted"; data: { content_length: number } } | { event: "DownloadProgress"; data: { finished_length: number; content_length: number; bytes_per_second: number; eta_secs: number | null } } | { event: "FileProgress"; data: FileProgress } | { event: "DownloadAttemptFailed"; data: AttemptFailure } | { event: "Installing" } | { event: "Extracting"; data: { finished_length: number; total_length: number } } | { event: "Copying"; data: { finished_length: number; total_length: number } } | { event: "Complete" } | { event: "Error"; data: string }
+export type LaunchStatus = { type: "NeedsGameDir" } | { type: "NeedsInstall" } | { type: "NeedsAndCanInstall"; data: { download_info: FileInstallConfig[] } } | { type: "NeedsAshita" } | { type: "NeedsWindower" } | { type: "NeedsUpdate"; data: { versions_info: VersionsInfo; 
+/**
+ * Components the server has an older version of, which need consent to be downgraded.
+ */
+downgrades: string[] } } | { type: "VerificationFailed"; data: { reason: string } } | { type: "UpdateCheckFailed"; data: { reason: string; error: ServerErrorDetails; policy: OfflinePolicy; 
+/**
+ * The last manifest fetched from the server, if any.
+ */
+last_known_versions: VersionsInfo | null; 
+/**
+ * Installed versions of the components in the last known manifest.
+ */
+installed_versions: Partial<{ [key in string]: string }>; needs_password: boolean } } | { type: "ClientVersionMismatch"; data: { reason: string; installed: ClientVersion | null; required: ClientRequirement; 
+/**
+ * Whether the mismatch is only a warning, and the game can still be launched.
+ */
+can_launch: boolean; needs_password: boolean } } | { type: "NeedsPassword" } | { type: "Ready" }
+export type NewsItem = { id: string; title: string; body?: string | null; 
+/**
+ * For example "maintenance" or "patch".
+ */
+kind?: string | null; published_at?: string | null; url?: string | null }
+export type OfflinePolicy = "LaunchAnyway" | "Block" | "Retry"
+export type ProbeResult = { online: boolean; latency_ms: number | null; error: string | null }
+export type Profile = { id: number; name?: string | null; server?: string | null; server_nickname?: string | null; server_info_addr?: string | null; 
+/**
+ * Minisign public key that server manifests and downloads must be signed with.
+ */
+server_public_key?: string | null; 
+/**
+ * PEM file with CA certificates to trust for a self-hosted HTTPS server info address.
+ */
+server_ca_certificate?: string | null; 
+/**
+ * Ashita version the server requires, from the server directory entry the profile was created from.
+ */
+required_ashita_version?: string | null; use_windower?: boolean; is_retail?: boolean; install?: InstallConfig; account_name?: string | null; password?: string | null; auth_kind?: AuthKind; manual_auth?: boolean; hairpin?: boolean; enable_gamepad?: boolean; enable_gamepad_background?: boolean; resolution?: Resolution; background_resolution?: Resolution; menu_resolution?: Resolution; start_pos_x?: number; start_pos_y?: number; enabled_addons?: string[] | null; enabled_plugins?: string[] | null; extra_pivots?: string[]; windower_profile?: string | null; 
+/**
+ * What to do when the server can't be asked for updates.
+ */
+offline_policy?: OfflinePolicy; 
+/**
+ * Server components pinned to a version, which won't be updated to any other version.
+ */
+pinned_versions?: Partial<{ [key in string]: string }>; 
+/**
+ * Older server versions of components the user chose to keep their installed version over.
+ */
+declined_downgrades?: Partial<{ [key in string]: string }> }
 export type Profiles = { ids?: number[]; map?: Partial<{ [key in number]: Profile }> }
+export type ProxySettings = { 
+/**
+ * Proxy URL, such as `http://proxy.example.com:8080` or `socks5h://localhost:1080`.
+ */
+url: string; username?: string | null; password?: string | null; 
+/**
+ * Comma separated hosts that are connected to directly, such as `localhost,.example.com`.
+ */
+no_proxy?: string | null }
+export type PruneReport = { removed_files: number; freed_bytes: number; remaining_bytes: number }
+export type ReadinessCheck = "GameDirectory" | "Frontend" | "Bootloader" | "ServerFiles" | "ClientVersion" | "PivotPlugin" | "Credentials" | "ScriptFile" | "ConfigWriteable"
+export type ReadinessFinding = { check: ReadinessCheck; severity: Severity; message: string; fix: FixAction | null }
+export type ReadinessReport = { 
+/**
+ * Whether none of the findings are errors.
+ */
+ready: boolean; findings: ReadinessFinding[] }
 export type Resolution = { width: number; height: number }
+export type ServerDiagnosis = { info_url: string | null; 
+/**
+ * Problems with the profile's server configuration itself.
+ */
+problems: string[]; endpoints: EndpointDiagnosis[] }
+export type ServerDirectoryEntry = { name: string; description?: string | null; hostname: string; info_addr?: string | null; website?: string | null; 
+/**
+ * Ashita version the server requires at least. Profiles created for the server use Ashita when set,
+ * and warn when the installed Ashita is older.
+ */
+ashita_version?: string | null; recommended_pivots?: string[]; 
+/**
+ * The server-list URL this entry came from. Filled in by the launcher.
+ */
+source?: string | null }
+export type ServerDirectoryFeed = { url: string; servers: ServerDirectoryEntry[]; 
+/**
+ * Unix timestamp of when the feed was last fetched successfully.
+ */
+fetched_at?: number | null; 
+/**
+ * Set when the latest fetch failed, and the servers come from the local cache.
+ */
+error?: string | null }
+/**
+ * A [ServerRequestError] in a form the frontend can show.
+ */
+export type ServerErrorDetails = { kind: ServerErrorKind; message: string; status: number | null; content_type: string | null; field: string | null }
+export type ServerErrorKind = "Unavailable" | "Status" | "InvalidResponse" | "Verification"
+export type ServerNews = { items: ServerNewsItem[]; unread_count: number; 
+/**
+ * Unix timestamp of when the news was last fetched successfully.
+ */
+fetched_at: number | null; 
+/**
+ * Set when the latest fetch failed, and the items come from the local cache.
+ */
+error: string | null }
+export type ServerNewsItem = { item: NewsItem; read: boolean }
+export type ServerStatus = { profile_id: number; online: boolean; 
+/**
+ * Whether the server info endpoints are only offered over plain HTTP.
+ */
+plain_http: boolean; login: ProbeResult; info: ProbeResult; status: ServerStatusInfo | null }
+/**
+ * Optional information a server can publish at `/status` on its info address.
+ */
+export type ServerStatusInfo = { players?: number | null; motd?: string | null }
+export type Severity = "Ok" | "Info" | "Warning" | "Error"
 export type TAURI_CHANNEL<TSend> = null
-export type UpdateTaskMessage = { event: "DownloadPending"; data: { id: string } } | { event: "DownloadStarted"; data: { id: string; content_length: number } } | { event: "DownloadProgress"; data: { id: string; finished_length: number } } | { event: "DownloadFinished"; data: { id: string } } | { event: "UnpackPending"; data: { id: string } } | { event: "UnpackFinished"; data: { id: string } } | { event: "FailedSpecific"; data: { id: string } } | { event: "Done" }
-export type VersionInfo = { url: string; version: string }
-export type VersionsInfo = { dats?: VersionInfo | null; bootloader?: VersionInfo | null }
+export type UpdateTaskMessage = { event: "DownloadPending"; data: { id: string } } | { event: "DownloadStarted"; data: { id: string; content_length: number } } | { event: "DownloadProgress"; data: { id: string; finished_length: number; total_length: number | null; bytes_per_second: number; eta_secs: number | null } } | { event: "DownloadFinished"; data: { id: string } } | { event: "UnpackPending"; data: { id: string } } | { event: "UnpackFinished"; data: { id: string } } | { event: "FailedSpecific"; data: { id: string } } | { event: "Done" }
+export type VersionInfo = { url: string; version: string; size?: number | null; sha256?: string | null; 
+/**
+ * Detached minisign signature of the download.
+ */
+signature?: string | null }
+export type VersionsInfo = { dats?: VersionInfo | null; bootloader?: VersionInfo | null; 
+/**
+ * Named components of a v2 manifest, which are installed alongside `dats` and `bootloader`.
+ */
+components?: ComponentInfo[]; 
+/**
+ * Client version the server expects players to have installed.
+ */
+client?: ClientRequirement | null }
 
 /** tauri-specta globals **/
 
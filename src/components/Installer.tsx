@@ -26,6 +26,7 @@ const Installer = (props: InstallerProps) => {
   const [getIsInstalling, setIsInstalling] = createSignal<boolean>(false);
   const [status, setStatus] = createStore<InstallStatus>({});
   const [getFolderName, setFolderName] = createSignal<string>("");
+  const [getLocalSource, setLocalSource] = createSignal<string>("");
 
   const requiresFolderName = createMemo(() => {
     const directory = props.profile.install?.directory;
@@ -91,6 +92,7 @@ const Installer = (props: InstallerProps) => {
         case "DownloadProgress":
           setStatus(produce(status => {
             status.current_progress = message.data.finished_length;
+            status.expected_final = message.data.content_length;
           }));
           break;
         case "Installing":
@@ -99,6 +101,15 @@ const Installer = (props: InstallerProps) => {
             status.unknown_progress = true;
           }));
           break;
+        case "Extracting":
+        case "Copying":
+          setStatus(produce(status => {
+            status.state = message.event == "Copying" ? "Copying" : "Installing";
+            status.unknown_progress = false;
+            status.current_progress = message.data.finished_length;
+            status.expected_final = message.data.total_length;
+          }));
+          break;
         case "Error":
           toast.error(`Failed to install: ${message.data}`);
           setStatus({});
@@ -111,7 +122,8 @@ const Installer = (props: InstallerProps) => {
       }
     };
 
-    let result = await commands.installGameForProfile(props.profile.id, props.downloadInfo, channel);
+    const localSource = getLocalSource().trim() || null;
+    let result = await commands.installGameForProfile(props.profile.id, props.downloadInfo, localSource, channel);
     if (result.status == "error") {
       props.isComplete();
     }
@@ -167,12 +179,25 @@ const Installer = (props: InstallerProps) => {
           >
           </input>
         </div>
+        <div>
+          Already have the installer or a game client on this computer? Enter its folder to install without
+          downloading:
+        </div>
+        <div class="form field inline-flex items-center">
+          <input
+            type="text"
+            value={getLocalSource()}
+            onInput={e => setLocalSource(e.target.value)}
+            placeholder="Folder with the installer or game client (optional)"
+          >
+          </input>
+        </div>
         <button
           class="button accept"
           onClick={startInstall}
           disabled={getIsInstalling()}
         >
-          Download and install
+          {getLocalSource().trim() ? "Install from local files" : "Download and install"}
         </button>
       </Show>
 
@@ -15,6 +15,7 @@ import { createStore, produce, unwrap } from "solid-js/store";
 import { AuthKind, commands, Profile } from "../bindings";
 import { useData } from "../store";
 import { DEFAULT_ADDONS, DEFAULT_PLUGINS } from "../store/profiles";
+import { UNVERIFIED_HTTP_WARNING, usesUnverifiedHttp } from "../util";
 import FileInput from "./FileInput";
 import Modal from "./Modal";
 import ResolutionInput from "./ResolutionInput";
@@ -226,6 +227,25 @@ const ProfileEditor = () => {
             </input>
           </div>
 
+          <div class="field half">
+            <label class="label" for="server_info_addr">
+              Server info address
+            </label>
+            <input
+              id="server_info_addr"
+              type="text"
+              placeholder={`${profile.server || "localhost"}:15850`}
+              value={profile.server_info_addr ?? ""}
+              onInput={e => updateProfileInfo("server_info_addr", e.target.value.trim() || null)}
+            >
+            </input>
+            <Show when={usesUnverifiedHttp(profile)}>
+              <div class="text-yellow-200">
+                {UNVERIFIED_HTTP_WARNING} Use an https:// address if the server supports it.
+              </div>
+            </Show>
+          </div>
+
           <div class="field half">
             <div class="half">
               <input
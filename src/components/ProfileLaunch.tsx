@@ -1,10 +1,10 @@
 import { useNavigate, useParams } from "@solidjs/router";
-import { createEffect, createResource, createSignal, Match, Switch } from "solid-js";
+import { createEffect, createResource, createSignal, Match, Show, Switch } from "solid-js";
 import { unwrap } from "solid-js/store";
 import toast from "solid-toast";
 import { commands } from "../bindings";
 import { useData } from "../store";
-import { unwrapResult } from "../util";
+import { UNVERIFIED_HTTP_WARNING, unwrapResult, usesUnverifiedHttp } from "../util";
 import Installer from "./Installer";
 import LoginPrompt from "./LoginPrompt";
 import Updater from "./Updater";
@@ -57,6 +57,31 @@ const ProfileLaunch = () => {
     }
   };
 
+  const launchControls = (needsPassword: boolean) => (
+    <Show
+      when={!needsPassword}
+      fallback={
+        <LoginPrompt
+          callback={password => {
+            launch(id, password);
+          }}
+        >
+        </LoginPrompt>
+      }
+    >
+      <form
+        onSubmit={e => {
+          e.preventDefault();
+          launch(id);
+        }}
+      >
+        <button class="button accept" type="submit">
+          Launch
+        </button>
+      </form>
+    </Show>
+  );
+
   const actionComponent = () => {
     const result = resource();
     if (!result) {
@@ -109,12 +134,61 @@ const ProfileLaunch = () => {
         return (
           <Updater
             id={id}
+            downgrades={result.data.data.downgrades}
             isComplete={() => {
               refetchStatus();
             }}
           />
         );
 
+      case "VerificationFailed":
+        setAutoLaunch(false);
+        return (
+          <div class="flex flex-col w-full items-center gap-2">
+            <div>The server's files could not be verified, so the game can't be launched.</div>
+            <code class="text-red-300">{result.data.data.reason}</code>
+          </div>
+        );
+
+      case "UpdateCheckFailed":
+        setAutoLaunch(false);
+        return (
+          <div class="flex flex-col w-full items-center gap-2">
+            <div>The server couldn't be checked for updates, so the installed files may be out of date.</div>
+            <code class="text-red-300">{result.data.data.reason}</code>
+            <Show
+              when={result.data.data.policy !== "Block"}
+              fallback={
+                <>
+                  <div>This profile doesn't allow launching until the server can be reached.</div>
+                  <button class="button accept" onClick={() => refetchStatus()}>
+                    Try again
+                  </button>
+                </>
+              }
+            >
+              {launchControls(result.data.data.needs_password)}
+            </Show>
+          </div>
+        );
+
+      case "ClientVersionMismatch":
+        setAutoLaunch(false);
+        return (
+          <div class="flex flex-col w-full items-center gap-2">
+            <div>The installed client doesn't match the version the server expects.</div>
+            <code class={result.data.data.can_launch ? "text-yellow-200" : "text-red-300"}>
+              {result.data.data.reason}
+            </code>
+            <Show
+              when={result.data.data.can_launch}
+              fallback={<div>Update the client before launching the game.</div>}
+            >
+              {launchControls(result.data.data.needs_password)}
+            </Show>
+          </div>
+        );
+
       case "NeedsPassword":
         return (
           <LoginPrompt
@@ -130,24 +204,16 @@ const ProfileLaunch = () => {
         if (getAutoLaunch()) {
           launch(id);
         } else {
-          return (
-            <form
-              onSubmit={e => {
-                e.preventDefault();
-                launch(id);
-              }}
-            >
-              <button class="button accept" type="submit">
-                Launch
-              </button>
-            </form>
-          );
+          return launchControls(false);
         }
     }
   };
 
   return (
     <div class="w-full h-full flex flex-col items-center">
+      <Show when={usesUnverifiedHttp(profile)}>
+        <div class="w-full my-2 text-yellow-200">{UNVERIFIED_HTTP_WARNING}</div>
+      </Show>
       <div class="w-full flex-grow">
         <Switch>
           <Match when={resource.loading}>
@@ -13,11 +13,11 @@ interface UpdateStatus {
   expected_final?: number;
 }
 
-const Updater = (props: { id: number; isComplete: () => any; }) => {
+const Updater = (props: { id: number; downgrades: string[]; isComplete: () => any; }) => {
   const [getIsUpdating, setIsUpdating] = createSignal<boolean>(false);
   const [status, setStatus] = createStore<UpdateStatus>({});
 
-  const startUpdate = async () => {
+  const startUpdate = async (allowDowngrade: boolean) => {
     if (getIsUpdating()) {
       return;
     }
@@ -73,7 +73,7 @@ const Updater = (props: { id: number; isComplete: () => any; }) => {
       }
     };
 
-    unwrapResult(await commands.updateProfileServerFiles(props.id, channel));
+    unwrapResult(await commands.updateProfileServerFiles(props.id, allowDowngrade, channel));
   };
 
   const percentageComplete = createMemo(() => {
@@ -86,18 +86,47 @@ const Updater = (props: { id: number; isComplete: () => any; }) => {
 
   return (
     <div class="flex flex-col w-full items-center">
-      <div>An update is available.</div>
+      <Show
+        when={props.downgrades.length > 0}
+        fallback={<div>An update is available.</div>}
+      >
+        <div class="text-center">
+          The server has older versions of {props.downgrades.join(", ")} than are installed.
+        </div>
+      </Show>
 
       <Show
         when={status.state !== undefined}
         fallback={
-          <button
-            class="button accept"
-            onClick={startUpdate}
-            disabled={getIsUpdating()}
+          <Show
+            when={props.downgrades.length > 0}
+            fallback={
+              <button
+                class="button accept"
+                onClick={() => startUpdate(false)}
+                disabled={getIsUpdating()}
+              >
+                Update
+              </button>
+            }
           >
-            Update
-          </button>
+            <div class="flex gap-2">
+              <button
+                class="button accept"
+                onClick={() => startUpdate(true)}
+                disabled={getIsUpdating()}
+              >
+                Downgrade
+              </button>
+              <button
+                class="button neutral"
+                onClick={() => startUpdate(false)}
+                disabled={getIsUpdating()}
+              >
+                Keep installed versions
+              </button>
+            </div>
+          </Show>
         }
       >
         <div>
@@ -1,6 +1,6 @@
 import { open } from "@tauri-apps/plugin-dialog";
 import toast from "solid-toast";
-import { Result } from "./bindings";
+import { Profile, Result } from "./bindings";
 
 export async function promptFolder(
   setFolder: (path: string | null) => any,
@@ -26,6 +26,20 @@ export function unwrapResult<T, E>(result: Result<T, E>): T {
   return result.data;
 }
 
+/**
+ * Whether the profile's server files are fetched over plain HTTP without a pinned key to verify them,
+ * so they could be tampered with. Info addresses without a scheme use plain HTTP.
+ */
+export function usesUnverifiedHttp(profile: Profile): boolean {
+  if (profile.is_retail || profile.server_public_key) {
+    return false;
+  }
+  return !profile.server_info_addr?.trim().toLowerCase().startsWith("https://");
+}
+
+export const UNVERIFIED_HTTP_WARNING =
+  "Server files are downloaded over plain HTTP without a server key to verify them, so they could be tampered with.";
+
 const KILOBYTES = 1024;
 const MEGABYTES = KILOBYTES * 1024;
 const GIGABYTES = MEGABYTES * 1024;